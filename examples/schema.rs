@@ -0,0 +1,41 @@
+use std::env::current_dir;
+use std::fs::create_dir_all;
+
+use cosmwasm_schema::{export_schema, export_schema_with_title, remove_schemas, schema_for};
+
+use classic_terrapexc::trading::{ConfigResponse, InstantiateMsg, MigrateMsg, TraderListResponse};
+use terrapexc_trading::msg::{
+    BalancesResponse, ClaimsResponse, Cw20HookMsg, ExecuteMsg, HooksResponse, LastPriceResponse,
+    OrderGroupResponse, PairResponse, QueryMsg, ReverseSimulateResponse, SimulateOrderResponse,
+    SimulateRouteResponse, SolvencyResponse, TradeHookExecuteMsg, TriggerListResponse,
+};
+
+fn main() {
+    let mut out_dir = current_dir().unwrap();
+    out_dir.push("schema");
+    create_dir_all(&out_dir).unwrap();
+    remove_schemas(&out_dir).unwrap();
+
+    // InstantiateMsg and MigrateMsg are still the shared package's, the execute and query
+    // messages are this contract's own and supersede classic_terrapexc::trading's.
+    export_schema(&schema_for!(InstantiateMsg), &out_dir);
+    export_schema(&schema_for!(MigrateMsg), &out_dir);
+    export_schema_with_title(&schema_for!(ExecuteMsg), &out_dir, "ExecuteMsg");
+    export_schema_with_title(&schema_for!(QueryMsg), &out_dir, "QueryMsg");
+    export_schema(&schema_for!(Cw20HookMsg), &out_dir);
+    export_schema(&schema_for!(TradeHookExecuteMsg), &out_dir);
+
+    export_schema(&schema_for!(ConfigResponse), &out_dir);
+    export_schema(&schema_for!(TraderListResponse), &out_dir);
+    export_schema(&schema_for!(TriggerListResponse), &out_dir);
+    export_schema(&schema_for!(LastPriceResponse), &out_dir);
+    export_schema(&schema_for!(OrderGroupResponse), &out_dir);
+    export_schema(&schema_for!(PairResponse), &out_dir);
+    export_schema(&schema_for!(BalancesResponse), &out_dir);
+    export_schema(&schema_for!(SolvencyResponse), &out_dir);
+    export_schema(&schema_for!(ClaimsResponse), &out_dir);
+    export_schema(&schema_for!(SimulateRouteResponse), &out_dir);
+    export_schema(&schema_for!(SimulateOrderResponse), &out_dir);
+    export_schema(&schema_for!(ReverseSimulateResponse), &out_dir);
+    export_schema(&schema_for!(HooksResponse), &out_dir);
+}
@@ -0,0 +1,83 @@
+use cosmwasm_std::{attr, Deps, DepsMut, MessageInfo, Order, Reply, Response, StdResult, Storage};
+
+use crate::error::ContractError;
+use crate::ledger::{add_liability, asset_denom, asset_key, remove_liability};
+use crate::msg::{AssetClaim, ClaimsResponse};
+use crate::state::{CLAIMS, PENDING_PAYOUTS};
+use crate::util;
+
+use classic_terrapexc::asset::AssetInfo;
+
+// maker payouts reply with this id plus their index in PENDING_PAYOUTS
+pub const PAYOUT_REPLY_ID: u64 = 1000;
+
+//////////////////////////////////////////////////
+// Description:  Pay out what failed to reach the sender when its orders filled
+// Params: [1] - Asset to claim
+/////////////////////////////////////////////////
+pub fn execute_claim(
+    deps: DepsMut,
+    info: MessageInfo,
+    asset: AssetInfo,
+) -> Result<Response, ContractError> {
+    let key = (info.sender.clone(), asset_key(&asset));
+    let amount = CLAIMS
+        .may_load(deps.storage, key.clone())?
+        .unwrap_or_default();
+    if amount.is_zero() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+    CLAIMS.remove(deps.storage, key);
+    remove_liability(deps.storage, &asset_key(&asset), amount)?;
+
+    let denom = asset_denom(deps.api, &asset)?;
+    let message = util::transfer_token_message(denom, amount, info.sender.clone())?;
+
+    Ok(Response::new().add_message(message).add_attributes(vec![
+        attr("action", "claim"),
+        attr("address", info.sender),
+        attr("asset", asset_key(&asset)),
+        attr("amount", amount),
+    ]))
+}
+
+// Reply of a maker payout that failed. The contract still holds the proceeds,
+// they stay claimable by the maker and count as a liability until then.
+pub fn payout_failed(storage: &mut dyn Storage, msg: Reply) -> Result<Response, ContractError> {
+    let index = (msg.id - PAYOUT_REPLY_ID) as usize;
+    let payout = match PENDING_PAYOUTS.load(storage)?.get(index) {
+        Some(payout) => payout.clone(),
+        None => return Err(ContractError::InvalidInput {}),
+    };
+
+    let asset = payout.token.to_string();
+    let key = (payout.recipient.clone(), asset.clone());
+    let claim = CLAIMS.may_load(storage, key.clone())?.unwrap_or_default();
+    CLAIMS.save(storage, key, &(claim + payout.amount))?;
+    add_liability(storage, &asset, payout.amount)?;
+
+    let error = msg.result.into_result().err().unwrap_or_default();
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "payout_failed"),
+        attr("address", payout.recipient),
+        attr("asset", asset),
+        attr("amount", payout.amount),
+        attr("error", error),
+    ]))
+}
+
+pub fn query_claims(deps: Deps, address: String) -> StdResult<ClaimsResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let claims = CLAIMS
+        .prefix(address.clone())
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            item.map(|(asset, amount)| AssetClaim {
+                asset: String::from_utf8(asset).unwrap(),
+                amount,
+            })
+        })
+        .collect::<StdResult<Vec<AssetClaim>>>()?;
+
+    Ok(ClaimsResponse { address, claims })
+}
@@ -2,21 +2,57 @@ use crate::error::ContractError;
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    attr, from_binary, to_binary, Addr, Api, Binary, CosmosMsg, Deps, DepsMut, Env, MessageInfo,
-    Order, QuerierWrapper, Response, StdResult, Storage, Uint128,
+    attr, from_binary, to_binary, Addr, Api, Attribute, Binary, Deps, DepsMut, Env, Event,
+    MessageInfo, Order, QuerierWrapper, Reply, Response, StdResult, Storage, Uint128,
 };
 use cw2::set_contract_version;
-use cw20::{Cw20ReceiveMsg, Denom};
-use cw_storage_plus::Bound;
-
-use crate::state::{Config, BUYERS, CONFIG, SELLERS};
+use cw20::Cw20ReceiveMsg;
+use cw_storage_plus::{Bound, U64Key, U8Key};
+
+use crate::claim::{execute_claim, payout_failed, query_claims, PAYOUT_REPLY_ID};
+use crate::events::{self, Fill};
+use crate::factory::{execute_add_pair, pair_instantiated, INSTANTIATE_PAIR_REPLY_ID};
+use crate::group::{
+    check_group_id, execute_place_bracket, execute_place_oco, on_order_cancelled, on_order_filled,
+};
+use crate::hooks::{
+    execute_add_global_hook, execute_add_hook, execute_remove_global_hook, execute_remove_hook,
+    hook_failed, notify_hooks, query_hooks, HOOK_REPLY_ID,
+};
+use crate::iceberg::{execute_place_iceberg, refill_iceberg};
+use crate::ledger::{
+    check_ledger_funds, deposit_received, execute_deposit, execute_deposit_token,
+    execute_receive_deposit, execute_reconcile, execute_withdraw, lock_order, query_balances,
+    query_solvency, release_order, DEPOSIT_REPLY_ID,
+};
+use crate::matching::{prevent_self_trade, record_last_price, settle_fill, NetTransfers};
+use crate::msg::{
+    CounterOrder, Cw20HookMsg, ExecuteMsg, LastPriceResponse, MatchOutcome, MatchStatus,
+    OrderGroupResponse, OrderResponse, QueryMsg, SelfTradePrevention, TriggerInfo,
+    TriggerListResponse,
+};
+use crate::pair::{
+    check_circuit_breaker, check_pair_status, check_price_band, execute_update_pair,
+    load_pair_settings, query_pair, PairAction,
+};
+use crate::route::{execute_route, query_simulate_route};
+use crate::simulate::{query_reverse_simulate, query_simulate_order};
+use crate::state::{
+    pair_key, side_key, Config, IndexMigration, TriggerOrder, BUYERS, CONFIG, GROUP_OF, ICEBERGS,
+    INDEX_MIGRATION, LAST_PRICES, ORDERS_OF, ORDER_GROUPS, ORDER_INDEX, ORDER_PRIORITY,
+    PRIORITY_SEQ, SELLERS, TRIGGERS,
+};
+use crate::trigger::{
+    add_trigger, execute_cancel_trigger, execute_execute_triggers, execute_place_trailing_stop,
+    execute_place_trigger, process_triggers, remove_trigger, DEFAULT_TRIGGER_LIMIT,
+};
 use crate::util;
 
 use classic_terrapexc::asset::AssetInfo;
 use classic_terrapexc::querier::{query_balance, query_token_balance};
 use classic_terrapexc::trading::{
-    ConfigResponse, ExecuteMsg, InstantiateMsg, MatchOrderResponse, MigrateMsg, PairInfo, QueryMsg,
-    ReceiveMsg, TraderInfo, TraderListResponse, TraderRecord,
+    ConfigResponse, InstantiateMsg, MatchOrderResponse, MigrateMsg, PairInfo, TraderInfo,
+    TraderListResponse, TraderRecord,
 };
 
 pub const NORMAL_DECIMAL: u128 = 1000000u128;
@@ -37,6 +73,9 @@ pub fn instantiate(
         owner: deps.api.addr_canonicalize(info.sender.as_str())?,
         pair_list: msg.pair_list,
         enabled: msg.enabled,
+        ledger: false,
+        treasury: None,
+        pair_code_id: None,
     };
 
     CONFIG.save(deps.storage, &config)?;
@@ -50,21 +89,188 @@ pub fn execute(
     env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    let DepsMut {
+        storage,
+        api,
+        querier,
+    } = deps;
+    let deps = DepsMut {
+        storage: &mut *storage,
+        api,
+        querier,
+    };
+    let res = dispatch(deps, env, info, msg)?;
+    Ok(notify_hooks(storage, res)?)
+}
+
+fn dispatch(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
     match msg {
         ExecuteMsg::UpdateConfig {
             owner,
             pair_list,
             enabled,
-        } => execute_update_config(deps, env, info, owner, pair_list, enabled),
-        ExecuteMsg::Receive(msg) => execute_receive(deps, msg),
+            ledger,
+            treasury,
+            pair_code_id,
+        } => execute_update_config(
+            deps,
+            env,
+            info,
+            owner,
+            pair_list,
+            enabled,
+            ledger,
+            treasury,
+            pair_code_id,
+        ),
+        ExecuteMsg::AddPair { pair_info } => execute_add_pair(deps, env, info, pair_info),
+        ExecuteMsg::Receive(msg) => execute_receive(deps, env, info, msg),
+        ExecuteMsg::Deposit {} => execute_deposit(deps, info),
+        ExecuteMsg::DepositToken { token, amount } => {
+            execute_deposit_token(deps, env, info, token, amount)
+        }
+        ExecuteMsg::Withdraw { asset, amount } => execute_withdraw(deps, info, asset, amount),
+        ExecuteMsg::Claim { asset } => execute_claim(deps, info, asset),
+        ExecuteMsg::Reconcile { asset } => execute_reconcile(deps, env, info, asset),
+        ExecuteMsg::UpdatePair {
+            pair_id,
+            status,
+            self_trade_prevention,
+            price_band,
+            circuit_breaker,
+            token_transfer_mode,
+        } => execute_update_pair(
+            deps,
+            info,
+            pair_id,
+            status,
+            self_trade_prevention,
+            price_band,
+            circuit_breaker,
+            token_transfer_mode,
+        ),
         ExecuteMsg::Order {
             order,
             add_order,
             update_order,
-            remove_orders
-        } => execute_order(deps, order, add_order, update_order, remove_orders),
+            remove_orders,
+            self_trade_prevention,
+        } => execute_order(
+            deps,
+            env,
+            info,
+            order,
+            add_order,
+            update_order,
+            remove_orders,
+            self_trade_prevention,
+        ),
         ExecuteMsg::Cancel { order_id, is_buy } => execute_cancel(deps, info, order_id, is_buy),
+        ExecuteMsg::CancelOrders { order_ids } => execute_cancel_orders(deps, info, order_ids),
+        ExecuteMsg::CancelAll { pair_id, limit } => execute_cancel_all(deps, info, pair_id, limit),
+        ExecuteMsg::ForceCancel { pair_id, limit } => {
+            execute_force_cancel(deps, info, pair_id, limit)
+        }
+        ExecuteMsg::MigrateIndex { limit } => execute_migrate_index(deps, limit),
+        ExecuteMsg::AmendOrder {
+            order_id,
+            new_price,
+            new_quantity,
+        } => execute_amend_order(deps, env, info, order_id, new_price, new_quantity),
+        ExecuteMsg::PlaceTrigger {
+            order,
+            trigger_type,
+            trigger_price,
+            order_type,
+        } => execute_place_trigger(
+            deps,
+            env,
+            info,
+            order,
+            trigger_type,
+            trigger_price,
+            order_type,
+        ),
+        ExecuteMsg::PlaceTrailingStop {
+            order,
+            offset,
+            order_type,
+        } => execute_place_trailing_stop(deps, env, info, order, offset, order_type),
+        ExecuteMsg::CancelTrigger { pair_id, order_id } => {
+            execute_cancel_trigger(deps, info, pair_id, order_id)
+        }
+        ExecuteMsg::ExecuteTriggers { pair_id, limit } => {
+            execute_execute_triggers(deps, env, info, pair_id, limit)
+        }
+        ExecuteMsg::PlaceOco {
+            limit_order,
+            stop_order,
+            stop_price,
+            stop_order_type,
+        } => execute_place_oco(
+            deps,
+            env,
+            info,
+            limit_order,
+            stop_order,
+            stop_price,
+            stop_order_type,
+        ),
+        ExecuteMsg::PlaceIceberg {
+            order,
+            display_quantity,
+        } => execute_place_iceberg(deps, env, info, order, display_quantity),
+        ExecuteMsg::PlaceBracket {
+            entry,
+            take_profit_price,
+            stop_price,
+            stop_order_type,
+        } => execute_place_bracket(
+            deps,
+            env,
+            info,
+            entry,
+            take_profit_price,
+            stop_price,
+            stop_order_type,
+        ),
+        ExecuteMsg::Route {
+            offer_asset,
+            ask_asset,
+            amount,
+            min_receive,
+            path,
+        } => execute_route(
+            deps,
+            env,
+            info,
+            offer_asset,
+            ask_asset,
+            amount,
+            min_receive,
+            path,
+        ),
+        ExecuteMsg::AddHook { addr } => execute_add_hook(deps, info, addr),
+        ExecuteMsg::RemoveHook { addr } => execute_remove_hook(deps, info, addr),
+        ExecuteMsg::AddGlobalHook { addr } => execute_add_global_hook(deps, info, addr),
+        ExecuteMsg::RemoveGlobalHook { addr } => execute_remove_global_hook(deps, info, addr),
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    match msg.id {
+        HOOK_REPLY_ID => Ok(hook_failed(msg)),
+        INSTANTIATE_PAIR_REPLY_ID => pair_instantiated(deps, msg),
+        DEPOSIT_REPLY_ID => deposit_received(deps, env, msg),
+        id if id >= PAYOUT_REPLY_ID => payout_failed(deps.storage, msg),
+        _ => Err(ContractError::InvalidInput {}),
     }
 }
 
@@ -73,7 +279,11 @@ pub fn execute(
 // Params: [1] - Owner
 //         [2] - Trading Pair List
 //         [3] - Enabled
+//         [4] - Settle on the internal ledger
+//         [5] - Treasury
+//         [6] - Code id of per pair orderbook contracts
 /////////////////////////////////////////////////
+#[allow(clippy::too_many_arguments)]
 pub fn execute_update_config(
     deps: DepsMut,
     _env: Env,
@@ -81,6 +291,9 @@ pub fn execute_update_config(
     owner: Option<String>,
     pair_list: Option<Vec<PairInfo>>,
     enabled: Option<bool>,
+    ledger: Option<bool>,
+    treasury: Option<String>,
+    pair_code_id: Option<u64>,
 ) -> Result<Response, ContractError> {
     let mut config: Config = CONFIG.load(deps.storage)?;
 
@@ -101,6 +314,19 @@ pub fn execute_update_config(
         config.enabled = enabled;
     }
 
+    // orders resting from before a switch keep their locks, or the lack of them, until they leave
+    if let Some(ledger) = ledger {
+        config.ledger = ledger;
+    }
+
+    if let Some(treasury) = treasury {
+        config.treasury = Some(deps.api.addr_canonicalize(&treasury)?);
+    }
+
+    if let Some(pair_code_id) = pair_code_id {
+        config.pair_code_id = Some(pair_code_id);
+    }
+
     CONFIG.save(deps.storage, &config)?;
 
     Ok(Response::new().add_attribute("action", "update_config"))
@@ -112,6 +338,8 @@ pub fn execute_update_config(
 ///////////////////////////////////////////////////////////
 pub fn execute_receive(
     deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
     wrapper: Cw20ReceiveMsg,
 ) -> Result<Response, ContractError> {
     if wrapper.amount == Uint128::zero() {
@@ -119,106 +347,258 @@ pub fn execute_receive(
     }
     // let config: Config = CONFIG.load(deps.storage)?;
 
-    // let user_addr = &deps.api.addr_validate(&wrapper.sender)?;
-    // let msg: ReceiveMsg = from_binary(&wrapper.msg)?;
-    return Ok(Response::new());
+    let user_addr = deps.api.addr_validate(&wrapper.sender)?;
+    let msg: Cw20HookMsg = from_binary(&wrapper.msg)?;
+    match msg {
+        Cw20HookMsg::Deposit {} => {
+            execute_receive_deposit(deps, env, info, user_addr, wrapper.amount)
+        }
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn execute_order(
     deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
     order: TraderRecord,
     add_order: Option<TraderRecord>,
-    update_order: Option<TraderRecord>,
-    remove_orders: Option<Vec<TraderRecord>>,
+    update_order: Option<CounterOrder>,
+    remove_orders: Option<Vec<String>>,
+    self_trade_prevention: Option<SelfTradePrevention>,
 ) -> Result<Response, ContractError> {
+    check_enabled(&deps, &info)?;
     let cfg = CONFIG.load(deps.storage)?;
     let pair_info = cfg.pair_list.get(order.pair_id.u128() as usize).unwrap();
 
+    let now = env.block.time.seconds();
+
+    // orders are only taken and placed in the name of the sender
+    if order.address != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if let Some(add_order) = add_order.as_ref() {
+        if add_order.address != info.sender {
+            return Err(ContractError::Unauthorized {});
+        }
+    }
+
+    if let Some(add_order) = add_order.as_ref() {
+        check_pair_status(deps.storage, add_order.pair_id, PairAction::Place)?;
+        check_circuit_breaker(deps.storage, now, add_order.pair_id)?;
+        check_price_band(deps.storage, add_order.pair_id, add_order.price)?;
+    }
+    if update_order.is_some() || remove_orders.is_some() {
+        check_pair_status(deps.storage, order.pair_id, PairAction::Match)?;
+        check_circuit_breaker(deps.storage, now, order.pair_id)?;
+        check_price_band(deps.storage, order.pair_id, order.price)?;
+    }
+
     if order.current_stock_amount == Uint128::zero() {
         return Err(ContractError::InvalidZeroAmount {});
     }
-    // check out if buyer's balance is enough.
-    if order.is_buy {
-        let remains = match pair_info.from_asset.clone() {
-            AssetInfo::NativeToken { denom } => {
-                query_balance(&deps.querier, order.address.clone(), denom)?
-            }
-            AssetInfo::Token { contract_addr } => query_token_balance(
-                &deps.querier,
-                deps.api.addr_validate(contract_addr.as_str())?,
-                order.address.clone(),
-            )?,
-        };
-        let other_remains = remains * order.price / Uint128::from(NORMAL_DECIMAL);
-        if other_remains < order.current_stock_amount {
+    // the unfilled rest of the order rests on the same side of the same pair
+    if let Some(add_order) = add_order.as_ref() {
+        if add_order.pair_id != order.pair_id {
+            return Err(ContractError::AssetMismatch {});
+        }
+        if add_order.is_buy != order.is_buy {
             return Err(ContractError::InvalidInput {});
         }
-    } else {
-        let remains = match pair_info.to_asset.clone() {
-            AssetInfo::NativeToken { denom } => {
-                query_balance(&deps.querier, order.address.clone(), denom)?
+        check_group_id(deps.storage, &add_order.id)?;
+    }
+    check_order_funds(
+        deps.storage,
+        &deps.querier,
+        deps.api,
+        &env,
+        pair_info,
+        &order,
+    )?;
+
+    // transfers of all fills are netted into one message per (token, from, to)
+    let mut transfers = NetTransfers::new(&env.contract.address);
+    // (fill, maker price it executed at, maker order id)
+    let mut match_orders: Vec<(MatchOrderResponse, Uint128, String)> = vec![];
+    let mut stp_attributes: Vec<Attribute> = vec![];
+    let mut events: Vec<Event> = vec![];
+    // slices icebergs show once their visible part is filled, reported after the fills
+    let mut refills: Vec<Event> = vec![];
+
+    let stp = match self_trade_prevention {
+        Some(stp) => stp,
+        None => load_pair_settings(deps.storage, order.pair_id)?.self_trade_prevention,
+    };
+    // (counter order id, partial fill quantity or None to take the whole order)
+    let mut counter_orders: Vec<(String, Option<Uint128>)> = vec![];
+    if let Some(update_order) = update_order {
+        counter_orders.push((update_order.order_id, Some(update_order.quantity)));
+    }
+    if let Some(remove_orders) = remove_orders {
+        counter_orders.extend(remove_orders.into_iter().map(|order_id| (order_id, None)));
+    }
+
+    // counter orders rest on the other side, their stored records decide what trades
+    let book = if order.is_buy { &SELLERS } else { &BUYERS };
+    // stale counter orders are skipped or evicted, the rest of the batch still matches
+    let mut outcomes: Vec<MatchOutcome> = vec![];
+    let mut taker_cancelled = false;
+    let mut self_traded = Uint128::zero();
+    let mut filled = Uint128::zero();
+    for (order_id, quantity) in counter_orders.iter() {
+        if taker_cancelled {
+            outcomes.push(MatchOutcome::new(order_id, MatchStatus::Skipped));
+            continue;
+        }
+        let maker = match book.may_load(deps.storage, order_id.clone())? {
+            Some(maker) => maker,
+            None => {
+                outcomes.push(
+                    MatchOutcome::new(order_id, MatchStatus::Skipped)
+                        .with_reason(&ContractError::NotFindOrder {}),
+                );
+                continue;
             }
-            AssetInfo::Token { contract_addr } => query_token_balance(
-                &deps.querier,
-                deps.api.addr_validate(contract_addr.as_str())?,
-                order.address.clone(),
-            )?,
         };
-        if remains < order.current_stock_amount {
+        // a maker that moved away from the taker's price no longer trades with it
+        if let Err(err) = check_counter_order(&order, &maker) {
+            outcomes.push(MatchOutcome::new(order_id, MatchStatus::Skipped).with_reason(&err));
+            continue;
+        }
+        let amount = quantity.unwrap_or(maker.current_stock_amount);
+        if maker.address != order.address {
+            if let Err(err) = check_order_funds(
+                deps.storage,
+                &deps.querier,
+                deps.api,
+                &env,
+                pair_info,
+                &maker,
+            ) {
+                events.push(evict_order(deps.storage, &maker, &err)?);
+                outcomes.push(MatchOutcome::new(order_id, MatchStatus::Evicted).with_reason(&err));
+                continue;
+            }
+        }
+        filled += amount;
+        if filled > order.current_stock_amount {
             return Err(ContractError::InvalidInput {});
         }
-    }
 
-    let mut messages: Vec<CosmosMsg> = vec![];
-    let mut match_orders: Vec<MatchOrderResponse> = vec![];
-
-    if let Some(add_order) = add_order {
-        add_orderbook(deps.storage, add_order)?;
-    }
+        if maker.address == order.address {
+            stp_attributes.push(attr("self_trade_prevented", maker.id.clone()));
+            outcomes.push(MatchOutcome::new(order_id, MatchStatus::SelfTradePrevented));
+            if stp == SelfTradePrevention::DecrementAndCancel {
+                self_traded += amount;
+            }
+            let maker_cancelled = match stp {
+                SelfTradePrevention::CancelNewest => false,
+                SelfTradePrevention::CancelOldest | SelfTradePrevention::CancelBoth => true,
+                SelfTradePrevention::DecrementAndCancel => amount >= maker.current_stock_amount,
+            };
+            if maker_cancelled {
+                events.push(events::order_cancelled(&maker, "self_trade"));
+            }
+            if prevent_self_trade(deps.storage, &maker, amount, stp)? {
+                taker_cancelled = true;
+            }
+            continue;
+        }
 
-    if let Some(update_order) = update_order {
-        match_orders.push(update_orderbook(deps.storage, deps.api, deps.querier, &order, &update_order)?);
+        let result = match quantity {
+            None => remove_orderbook(deps.storage, &order, order_id),
+            Some(quantity) => update_orderbook(deps.storage, &order, order_id, *quantity),
+        };
+        match result {
+            Ok((match_order, price)) => {
+                // an iceberg taken whole shows its next slice
+                if quantity.is_none() {
+                    if let Some(shown) = book.may_load(deps.storage, order_id.clone())? {
+                        refills.push(events::order_placed(&shown, "iceberg"));
+                    }
+                }
+                outcomes.push(MatchOutcome {
+                    quantity: match_order.move_amount,
+                    price: Some(price),
+                    ..MatchOutcome::new(order_id, MatchStatus::Filled)
+                });
+                match_orders.push((match_order, price, order_id.clone()));
+            }
+            // the maker changed since the batch was built
+            Err(err @ ContractError::NotStarted {}) => {
+                filled -= amount;
+                outcomes.push(MatchOutcome::new(order_id, MatchStatus::Skipped).with_reason(&err));
+            }
+            Err(err) => return Err(err),
+        }
     }
 
-    if let Some(remove_orders) = remove_orders {
-        for remove_order in remove_orders.iter() {
-            match_orders.push(remove_orderbook(deps.storage, deps.api, deps.querier, &order, remove_order)?);
+    // the resting rest of a cancelled taker is dropped, a decremented one shrinks
+    if let Some(mut add_order) = add_order {
+        if !taker_cancelled {
+            add_order.current_stock_amount =
+                add_order.current_stock_amount.saturating_sub(self_traded);
+            if !add_order.current_stock_amount.is_zero() {
+                events.push(events::order_placed(&add_order, "book"));
+                add_orderbook(deps.storage, add_order)?;
+            }
         }
     }
 
-    for match_order in match_orders.iter() {
+    // fills settle at the maker's price, triggers of the pair fire against the last one
+    for (match_order, price, maker_order_id) in match_orders.iter() {
         let MatchOrderResponse {
             buyer,
             seller,
             move_amount,
         } = match_order;
-        
-        let other_move_amount =
-            move_amount * order.price / Uint128::from(NORMAL_DECIMAL);
-        if let AssetInfo::Token { contract_addr, .. } = &pair_info.to_asset {
-            let to_address = Addr::unchecked(contract_addr);
-            messages.push(util::transfer_from_token_message(
-                seller.clone(),
-                Denom::Cw20(to_address.clone()),
-                *move_amount,
-                buyer.clone(),
-            )?);
-        }
-        if let AssetInfo::Token { contract_addr, .. } = &pair_info.from_asset {
-            let from_address = Addr::unchecked(contract_addr);
-            messages.push(util::transfer_from_token_message(
-                buyer.clone(),
-                Denom::Cw20(from_address.clone()),
-                other_move_amount,
-                seller.clone(),
-            )?);
-        }
-    }
-
-    return Ok(Response::new().add_messages(messages).add_attributes(vec![
-        attr("action", "order"),
-        attr("address", order.address.clone()),
-    ]));
+        events.push(events::order_filled(&Fill {
+            pair_id: order.pair_id,
+            maker_order_id,
+            maker: if order.is_buy { seller } else { buyer },
+            taker_order_id: &order.id,
+            taker: &order.address,
+            taker_is_buy: order.is_buy,
+            price: *price,
+            quantity: *move_amount,
+        }));
+
+        settle_fill(
+            deps.storage,
+            &mut transfers,
+            pair_info,
+            buyer,
+            seller,
+            *move_amount,
+            *price,
+            order.is_buy,
+        )?;
+        record_last_price(deps.storage, now, order.pair_id, *price)?;
+    }
+    events.extend(refills);
+    let (trigger_attributes, trigger_events) = process_triggers(
+        deps.storage,
+        deps.api,
+        deps.querier,
+        &env,
+        &mut transfers,
+        pair_info,
+        order.pair_id,
+        DEFAULT_TRIGGER_LIMIT,
+    )?;
+    let messages = transfers.into_messages(deps.storage)?;
+    events.extend(trigger_events);
+
+    return Ok(Response::new()
+        .add_submessages(messages)
+        .add_attributes(vec![
+            attr("action", "order"),
+            attr("address", order.address.clone()),
+        ])
+        .add_attributes(stp_attributes)
+        .add_attributes(trigger_attributes)
+        .add_events(events)
+        .set_data(to_binary(&OrderResponse { outcomes })?));
 }
 
 pub fn execute_cancel(
@@ -239,21 +619,334 @@ pub fn execute_cancel(
         if record.address != info.sender.clone() {
             return Err(ContractError::Unauthorized {});
         }
-        BUYERS.remove(deps.storage, order_key.clone());
     } else {
         record = SELLERS.load(deps.storage, order_key.clone())?;
 
         if record.address != info.sender.clone() {
             return Err(ContractError::Unauthorized {});
         }
-        SELLERS.remove(deps.storage, order_key.clone());
     }
+    check_pair_status(deps.storage, record.pair_id, PairAction::Cancel)?;
+    cancel_resting_order(deps.storage, &record)?;
 
-    return Ok(Response::new().add_attributes(vec![attr("action", "cancel")]));
+    return Ok(Response::new()
+        .add_attributes(vec![attr("action", "cancel")])
+        .add_event(events::order_cancelled(&record, "user")));
 
     // should cancel approve
 }
 
+//////////////////////////////////////////////////
+// Description:  Cancel several own orders on either side
+// Params: [1] - Order ids, ids that no longer rest are skipped
+/////////////////////////////////////////////////
+pub fn execute_cancel_orders(
+    deps: DepsMut,
+    info: MessageInfo,
+    order_ids: Vec<String>,
+) -> Result<Response, ContractError> {
+    let mut events: Vec<Event> = vec![];
+    let mut skipped: Vec<Attribute> = vec![];
+    for order_id in order_ids {
+        // a fill or an OCO sibling may already have taken the order off the book
+        let record = match find_own_order(deps.storage, &info.sender, order_id) {
+            Ok(record) => record,
+            Err(ContractError::NotFindOrder {}) => continue,
+            Err(err) => return Err(err),
+        };
+        // orders of a pair that refuses cancels stay, the others are still cancelled
+        if check_pair_status(deps.storage, record.pair_id, PairAction::Cancel).is_err() {
+            skipped.push(attr("skipped", record.id));
+            continue;
+        }
+        cancel_resting_order(deps.storage, &record)?;
+        events.push(events::order_cancelled(&record, "user"));
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "cancel_orders")
+        .add_attributes(skipped)
+        .add_events(events))
+}
+
+//////////////////////////////////////////////////
+// Description:  Cancel own orders page by page, optionally of one pair only
+// Params: [1] - Pair id
+//         [2] - Max number of orders cancelled
+/////////////////////////////////////////////////
+pub fn execute_cancel_all(
+    deps: DepsMut,
+    info: MessageInfo,
+    pair_id: Option<Uint128>,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+    // orders of a pair that refuses cancels are reported and left out of the page,
+    // so they cannot keep the rest from being cancelled
+    let mut order_keys: Vec<(bool, String)> = vec![];
+    let mut skipped: Vec<Attribute> = vec![];
+    for is_buy in [true, false] {
+        let orders = ORDERS_OF
+            .prefix((info.sender.clone(), side_key(is_buy)))
+            .range(deps.storage, None, None, Order::Ascending);
+        for item in orders {
+            if order_keys.len() >= limit {
+                break;
+            }
+            let (id, order_pair_id) = item?;
+            if pair_id.is_some() && pair_id != Some(order_pair_id) {
+                continue;
+            }
+            let order_id = String::from_utf8(id).unwrap();
+            if check_pair_status(deps.storage, order_pair_id, PairAction::Cancel).is_err() {
+                skipped.push(attr("skipped", order_id));
+                continue;
+            }
+            order_keys.push((is_buy, order_id));
+        }
+    }
+
+    let mut events: Vec<Event> = vec![];
+    for (is_buy, order_id) in order_keys.iter() {
+        let book = if *is_buy { &BUYERS } else { &SELLERS };
+        // an OCO sibling cancelled earlier in the page is already gone
+        let record = match book.may_load(deps.storage, order_id.clone())? {
+            Some(record) => record,
+            None => continue,
+        };
+        cancel_resting_order(deps.storage, &record)?;
+        events.push(events::order_cancelled(&record, "user"));
+    }
+
+    Ok(Response::new()
+        .add_attributes(vec![
+            attr("action", "cancel_all"),
+            attr("removed", events.len().to_string()),
+            attr("done", (order_keys.len() < limit).to_string()),
+        ])
+        .add_attributes(skipped)
+        .add_events(events))
+}
+
+//////////////////////////////////////////////////
+// Description:  Only owner can execute it, clears the book of a pair page by page
+// Params: [1] - Pair id
+//         [2] - Max number of orders removed
+/////////////////////////////////////////////////
+pub fn execute_force_cancel(
+    deps: DepsMut,
+    info: MessageInfo,
+    pair_id: Uint128,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    check_owner(&deps, &info)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+    // each side is paged through the pair's price index, orders of other pairs are never read
+    let mut records: Vec<TraderRecord> = vec![];
+    for is_buy in [true, false] {
+        if records.len() >= limit {
+            break;
+        }
+        let order_ids = ORDER_INDEX
+            .prefix((pair_key(pair_id), side_key(is_buy)))
+            .range(deps.storage, None, None, Order::Ascending)
+            .take(limit - records.len())
+            .map(|item| item.map(|(_, order_id)| order_id))
+            .collect::<StdResult<Vec<String>>>()?;
+        let book = if is_buy { &BUYERS } else { &SELLERS };
+        for order_id in order_ids {
+            records.push(book.load(deps.storage, order_id)?);
+        }
+    }
+
+    let mut events: Vec<Event> = vec![];
+    for record in records.iter() {
+        let book = if record.is_buy { &BUYERS } else { &SELLERS };
+        if !book.has(deps.storage, record.id.clone()) {
+            continue;
+        }
+        cancel_resting_order(deps.storage, record)?;
+        events.push(events::order_cancelled(record, "forced"));
+    }
+
+    // resting triggers would otherwise fire into the wound down pair
+    let trigger_ids = TRIGGERS
+        .prefix(pair_key(pair_id))
+        .keys(deps.storage, None, None, Order::Ascending)
+        .take(limit - records.len())
+        .map(|key| String::from_utf8(key).unwrap())
+        .collect::<Vec<String>>();
+    for trigger_id in trigger_ids.iter() {
+        let key = (pair_key(pair_id), trigger_id.clone());
+        let trigger = match TRIGGERS.may_load(deps.storage, key)? {
+            Some(trigger) => trigger,
+            None => continue,
+        };
+        remove_trigger(deps.storage, &trigger);
+        on_order_cancelled(deps.storage, &trigger.order)?;
+        events.push(events::order_cancelled(&trigger.order, "forced"));
+    }
+
+    let removed = records.len() + trigger_ids.len();
+    Ok(Response::new()
+        .add_attributes(vec![
+            attr("action", "force_cancel"),
+            attr("pair_id", pair_id),
+            attr("removed", removed.to_string()),
+            attr("done", (removed < limit).to_string()),
+        ])
+        .add_events(events))
+}
+
+// Shrinks a resting order without a fill, cancelling it once nothing is left.
+pub fn reduce_resting_order(
+    storage: &mut dyn Storage,
+    record: &TraderRecord,
+    amount: Uint128,
+) -> StdResult<()> {
+    let book = if record.is_buy { &BUYERS } else { &SELLERS };
+    let mut record = book.load(storage, record.id.clone())?;
+    release_order(storage, &record, Some(amount))?;
+    record.current_stock_amount = record.current_stock_amount.saturating_sub(amount);
+    if record.current_stock_amount.is_zero() {
+        return cancel_resting_order(storage, &record);
+    }
+    book.save(storage, record.id.clone(), &record)
+}
+
+// Takes the order off the book, releasing its ledger lock, and cancels the rest of its OCO group.
+pub fn cancel_resting_order(storage: &mut dyn Storage, record: &TraderRecord) -> StdResult<()> {
+    remove_from_orderbook(storage, record.is_buy, record.id.clone())?;
+    on_order_cancelled(storage, record)
+}
+
+//////////////////////////////////////////////////
+// Description:  Amend a resting order in place
+// Params: [1] - Order id
+//         [2] - New price
+//         [3] - New remaining quantity
+/////////////////////////////////////////////////
+pub fn execute_amend_order(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    order_id: String,
+    new_price: Option<Uint128>,
+    new_quantity: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    check_enabled(&deps, &info)?;
+    if new_price.is_none() && new_quantity.is_none() {
+        return Err(ContractError::InvalidInput {});
+    }
+    let record = find_own_order(deps.storage, &info.sender, order_id.clone())?;
+    check_pair_status(deps.storage, record.pair_id, PairAction::Place)?;
+    check_circuit_breaker(deps.storage, env.block.time.seconds(), record.pair_id)?;
+    // an iceberg's visible slice is derived from its reserve,
+    // the legs of a group are sized together and replaced as a whole
+    if ICEBERGS.has(deps.storage, (side_key(record.is_buy), order_id.clone()))
+        || GROUP_OF.has(deps.storage, order_id.clone())
+    {
+        return Err(ContractError::InvalidInput {});
+    }
+
+    let price = new_price.unwrap_or(record.price);
+    let quantity = new_quantity.unwrap_or(record.current_stock_amount);
+    if price.is_zero() || quantity.is_zero() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+    if price != record.price {
+        check_price_band(deps.storage, record.pair_id, price)?;
+    }
+
+    let amended = TraderRecord {
+        price,
+        order_stock_amount: record.order_stock_amount - record.current_stock_amount + quantity,
+        current_stock_amount: quantity,
+        ..record.clone()
+    };
+
+    // orders are settled from the trader's balance, so a bigger commitment must still be covered
+    let commitment_grows =
+        quantity > record.current_stock_amount || record.is_buy && price > record.price;
+    if commitment_grows {
+        let cfg = CONFIG.load(deps.storage)?;
+        let pair_info = get_pair_info(&cfg.pair_list, record.pair_id)?;
+        check_order_funds(
+            deps.storage,
+            &deps.querier,
+            deps.api,
+            &env,
+            &pair_info,
+            &amended,
+        )?;
+    }
+
+    // the ledger lock follows the new commitment
+    release_order(deps.storage, &record, None)?;
+    lock_order(deps.storage, &amended, quantity)?;
+    if record.is_buy {
+        BUYERS.save(deps.storage, order_id.clone(), &amended)?;
+    } else {
+        SELLERS.save(deps.storage, order_id.clone(), &amended)?;
+    }
+    if price != record.price || quantity > record.current_stock_amount {
+        unindex_order(deps.storage, &record)?;
+        assign_priority(deps.storage, &amended)?;
+    }
+
+    Ok(Response::new()
+        .add_event(events::order_amended(&amended))
+        .add_attributes(vec![
+            attr("action", "amend_order"),
+            attr("order_id", order_id),
+            attr("price", price),
+            attr("quantity", quantity),
+        ]))
+}
+
+// The caller only names the resting order, its stored record decides what actually trades.
+// It must sit on the other side of the same pair at a price the taker crosses.
+fn check_counter_order(order: &TraderRecord, maker: &TraderRecord) -> Result<(), ContractError> {
+    if maker.pair_id != order.pair_id {
+        return Err(ContractError::AssetMismatch {});
+    }
+    if maker.is_buy == order.is_buy {
+        return Err(ContractError::InvalidInput {});
+    }
+    let crosses = if order.is_buy {
+        order.price >= maker.price
+    } else {
+        order.price <= maker.price
+    };
+    if !crosses {
+        return Err(ContractError::NotSamePrice {});
+    }
+    Ok(())
+}
+
+// Looks the order up on both sides of the book, only orders of `owner` match.
+pub fn find_own_order(
+    storage: &dyn Storage,
+    owner: &Addr,
+    order_id: String,
+) -> Result<TraderRecord, ContractError> {
+    let buy = BUYERS
+        .may_load(storage, order_id.clone())?
+        .filter(|record| record.address == *owner);
+    let sell = SELLERS
+        .may_load(storage, order_id)?
+        .filter(|record| record.address == *owner);
+
+    match (buy, sell) {
+        (Some(record), None) | (None, Some(record)) => Ok(record),
+        (None, None) => Err(ContractError::NotFindOrder {}),
+        // the same id on both sides, ambiguous without the side
+        (Some(_), Some(_)) => Err(ContractError::InvalidInput {}),
+    }
+}
+
 pub fn add_orderbook(
     storage: &mut dyn Storage,
     order: TraderRecord,
@@ -265,171 +958,332 @@ pub fn add_orderbook(
             return Err(ContractError::AlreadyStarted {});
         }
 
-        BUYERS.save(storage, key.clone(), &order)?;
+        lock_order(storage, &order, order.current_stock_amount)?;
+        BUYERS.save(storage, key, &order)?;
+        assign_priority(storage, &order)?;
         return Ok(true);
     } else {
         if SELLERS.has(storage, key.clone()) {
             return Err(ContractError::AlreadyStarted {});
         }
 
-        SELLERS.save(storage, key.clone(), &order)?;
+        lock_order(storage, &order, order.current_stock_amount)?;
+        SELLERS.save(storage, key, &order)?;
+        assign_priority(storage, &order)?;
         return Ok(true);
     }
 }
 
+// Puts the order at the back of the queue of its price level.
+pub fn assign_priority(storage: &mut dyn Storage, record: &TraderRecord) -> StdResult<()> {
+    unindex_order(storage, record)?;
+    let priority = PRIORITY_SEQ.may_load(storage)?.unwrap_or_default() + 1;
+    PRIORITY_SEQ.save(storage, &priority)?;
+    ORDER_PRIORITY.save(
+        storage,
+        (side_key(record.is_buy), record.id.clone()),
+        &priority,
+    )?;
+    index_order(storage, record, priority)
+}
+
+// Orders of a pair and side sort by price, best first, then by priority.
+fn order_index_key(record: &TraderRecord, priority: u64) -> (U64Key, U8Key, Vec<u8>) {
+    let price = if record.is_buy {
+        u128::MAX - record.price.u128()
+    } else {
+        record.price.u128()
+    };
+    let mut key = price.to_be_bytes().to_vec();
+    key.extend_from_slice(&priority.to_be_bytes());
+    (pair_key(record.pair_id), side_key(record.is_buy), key)
+}
+
+fn index_order(storage: &mut dyn Storage, record: &TraderRecord, priority: u64) -> StdResult<()> {
+    ORDERS_OF.save(
+        storage,
+        (
+            record.address.clone(),
+            side_key(record.is_buy),
+            record.id.clone(),
+        ),
+        &record.pair_id,
+    )?;
+    ORDER_INDEX.save(storage, order_index_key(record, priority), &record.id)
+}
+
+// Drops the index entry of the order at the price and priority it rests with.
+pub fn unindex_order(storage: &mut dyn Storage, record: &TraderRecord) -> StdResult<()> {
+    if let Some(priority) =
+        ORDER_PRIORITY.may_load(storage, (side_key(record.is_buy), record.id.clone()))?
+    {
+        ORDER_INDEX.remove(storage, order_index_key(record, priority));
+    }
+    Ok(())
+}
+
+pub fn remove_from_orderbook(
+    storage: &mut dyn Storage,
+    is_buy: bool,
+    key: String,
+) -> StdResult<()> {
+    let book = if is_buy { &BUYERS } else { &SELLERS };
+    if let Some(record) = book.may_load(storage, key.clone())? {
+        release_order(storage, &record, None)?;
+        unindex_order(storage, &record)?;
+        ORDERS_OF.remove(storage, (record.address, side_key(is_buy), key.clone()));
+    }
+    book.remove(storage, key.clone());
+    ORDER_PRIORITY.remove(storage, (side_key(is_buy), key.clone()));
+    ICEBERGS.remove(storage, (side_key(is_buy), key));
+    Ok(())
+}
+
+// Called once the resting quantity of a maker order is used up.
+// Icebergs show their next slice, anything else leaves the book. Returns true if the order left.
+pub fn finish_maker_order(storage: &mut dyn Storage, record: &TraderRecord) -> StdResult<bool> {
+    if refill_iceberg(storage, record)? {
+        return Ok(false);
+    }
+    remove_from_orderbook(storage, record.is_buy, record.id.clone())?;
+    Ok(true)
+}
+
 pub fn update_orderbook(
     storage: &mut dyn Storage,
-    api: &dyn Api,
-    querier: QuerierWrapper,
     order: &TraderRecord,
-    update_order: &TraderRecord,
-) -> Result<MatchOrderResponse, ContractError> {
-    let cfg = CONFIG.load(storage)?;
-    let pair_info = cfg.pair_list.get(update_order.pair_id.u128() as usize).unwrap();
-    let key = update_order.id.clone();
-    let move_amount = update_order.current_stock_amount;
-
-    if update_order.is_buy {
+    order_id: &str,
+    move_amount: Uint128,
+) -> Result<(MatchOrderResponse, Uint128), ContractError> {
+    let key = order_id.to_string();
+
+    if move_amount.is_zero() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+
+    // the counter order rests on the other side of the taker
+    if !order.is_buy {
         if !BUYERS.has(storage, key.clone()) {
             return Err(ContractError::NotStarted {});
         }
         let mut buyer_record = BUYERS.load(storage, key.clone())?;
-        
+        check_counter_order(order, &buyer_record)?;
+
         // check out if update is possible
         if move_amount >= buyer_record.current_stock_amount {
             return Err(ContractError::NotStarted {});
         }
 
-        // check out if buyer's balance is enough.
-        let remains = match pair_info.from_asset.clone() {
-            AssetInfo::NativeToken { denom } => {
-                query_balance(&querier, buyer_record.address.clone(), denom)?
-            }
-            AssetInfo::Token { contract_addr } => query_token_balance(
-                &querier,
-                api.addr_validate(contract_addr.as_str())?,
-                buyer_record.address.clone(),
-            )?,
-        };
-        let other_remains = remains / update_order.price * Uint128::from(NORMAL_DECIMAL);
-        if other_remains < move_amount {
-            return Err(ContractError::NotEnoughReward {});
-        }
-
+        release_order(storage, &buyer_record, Some(move_amount))?;
         buyer_record.current_stock_amount -= move_amount;
         BUYERS.save(storage, key.clone(), &buyer_record)?;
-
-        return Ok(MatchOrderResponse {
-            buyer: buyer_record.address.clone(),
-            seller: order.address.clone(),
-            move_amount: move_amount,
-        });
+        on_order_filled(storage, &buyer_record, move_amount, false)?;
+
+        return Ok((
+            MatchOrderResponse {
+                buyer: buyer_record.address.clone(),
+                seller: order.address.clone(),
+                move_amount: move_amount,
+            },
+            buyer_record.price,
+        ));
     } else {
         if !SELLERS.has(storage, key.clone()) {
             return Err(ContractError::NotStarted {});
         }
         let mut seller_record = SELLERS.load(storage, key.clone())?;
+        check_counter_order(order, &seller_record)?;
 
         // check out if update is possible
         if move_amount >= seller_record.current_stock_amount {
             return Err(ContractError::NotStarted {});
         }
 
-        // check out if seller's balance is enough.
-        let remains = match pair_info.to_asset.clone() {
-            AssetInfo::NativeToken { denom } => {
-                query_balance(&querier, seller_record.address.clone(), denom)?
-            }
-            AssetInfo::Token { contract_addr } => query_token_balance(
-                &querier,
-                api.addr_validate(contract_addr.as_str())?,
-                seller_record.address.clone(),
-            )?,
-        };
-        if remains < move_amount {
-            return Err(ContractError::NotEnoughReward {});
-        }
-
+        release_order(storage, &seller_record, Some(move_amount))?;
         seller_record.current_stock_amount -= move_amount;
         SELLERS.save(storage, key.clone(), &seller_record)?;
-
-        return Ok(MatchOrderResponse {
-            buyer: order.address.clone(),
-            seller: seller_record.address.clone(),
-            move_amount: move_amount,
-        });
+        on_order_filled(storage, &seller_record, move_amount, false)?;
+
+        return Ok((
+            MatchOrderResponse {
+                buyer: order.address.clone(),
+                seller: seller_record.address.clone(),
+                move_amount: move_amount,
+            },
+            seller_record.price,
+        ));
     }
 }
 
 pub fn remove_orderbook(
     storage: &mut dyn Storage,
-    api: &dyn Api,
-    querier: QuerierWrapper,
     order: &TraderRecord,
-    remove_order: &TraderRecord,
-) -> Result<MatchOrderResponse, ContractError> {
-    let cfg = CONFIG.load(storage)?;
-    let pair_info = cfg.pair_list.get(remove_order.pair_id.u128() as usize).unwrap();
-    let key = remove_order.id.clone();
-    let move_amount = remove_order.current_stock_amount;
-
-    if remove_order.is_buy {
+    order_id: &str,
+) -> Result<(MatchOrderResponse, Uint128), ContractError> {
+    let key = order_id.to_string();
+
+    // the counter order rests on the other side of the taker and is taken whole
+    if !order.is_buy {
         if !BUYERS.has(storage, key.clone()) {
             return Err(ContractError::NotStarted {});
         }
         let buyer_record = BUYERS.load(storage, key.clone())?;
+        check_counter_order(order, &buyer_record)?;
+        let move_amount = buyer_record.current_stock_amount;
+
+        release_order(storage, &buyer_record, Some(move_amount))?;
+        let fully_filled = finish_maker_order(storage, &buyer_record)?;
+        on_order_filled(storage, &buyer_record, move_amount, fully_filled)?;
+
+        return Ok((
+            MatchOrderResponse {
+                buyer: buyer_record.address.clone(),
+                seller: order.address.clone(),
+                move_amount: move_amount,
+            },
+            buyer_record.price,
+        ));
+    } else {
+        if !SELLERS.has(storage, key.clone()) {
+            return Err(ContractError::NotStarted {});
+        }
+        let seller_record = SELLERS.load(storage, key.clone())?;
+        check_counter_order(order, &seller_record)?;
+        let move_amount = seller_record.current_stock_amount;
+
+        release_order(storage, &seller_record, Some(move_amount))?;
+        let fully_filled = finish_maker_order(storage, &seller_record)?;
+        on_order_filled(storage, &seller_record, move_amount, fully_filled)?;
+
+        return Ok((
+            MatchOrderResponse {
+                buyer: order.address.clone(),
+                seller: seller_record.address.clone(),
+                move_amount: move_amount,
+            },
+            seller_record.price,
+        ));
+    }
+}
 
-        // check out if buyer's balance is enough.
+// check out if the trader's balance covers the order
+pub fn check_order_balance(
+    querier: &QuerierWrapper,
+    api: &dyn Api,
+    pair_info: &PairInfo,
+    order: &TraderRecord,
+) -> Result<(), ContractError> {
+    if order.is_buy {
         let remains = match pair_info.from_asset.clone() {
             AssetInfo::NativeToken { denom } => {
-                query_balance(&querier, buyer_record.address.clone(), denom)?
+                query_balance(querier, order.address.clone(), denom)?
             }
             AssetInfo::Token { contract_addr } => query_token_balance(
-                &querier,
+                querier,
                 api.addr_validate(contract_addr.as_str())?,
-                buyer_record.address.clone(),
+                order.address.clone(),
             )?,
         };
-        let other_remains = remains / remove_order.price * Uint128::from(NORMAL_DECIMAL);
-        if other_remains < move_amount {
-            return Err(ContractError::NotEnoughReward {});
+        let cost = order.current_stock_amount * order.price / Uint128::from(NORMAL_DECIMAL);
+        if remains < cost {
+            return Err(ContractError::InvalidInput {});
         }
-
-        BUYERS.remove(storage, key.clone());
-
-        return Ok(MatchOrderResponse {
-            buyer: buyer_record.address.clone(),
-            seller: order.address.clone(),
-            move_amount: move_amount,
-        });
     } else {
-        if !SELLERS.has(storage, key.clone()) {
-            return Err(ContractError::NotStarted {});
-        }
-        let seller_record = SELLERS.load(storage, key.clone())?;
-
-        // check out if seller's balance is enough.
         let remains = match pair_info.to_asset.clone() {
             AssetInfo::NativeToken { denom } => {
-                query_balance(&querier, seller_record.address.clone(), denom)?
+                query_balance(querier, order.address.clone(), denom)?
             }
             AssetInfo::Token { contract_addr } => query_token_balance(
-                &querier,
+                querier,
                 api.addr_validate(contract_addr.as_str())?,
-                seller_record.address.clone(),
+                order.address.clone(),
             )?,
         };
-        if remains < move_amount {
-            return Err(ContractError::NotEnoughReward {});
+        if remains < order.current_stock_amount {
+            return Err(ContractError::InvalidInput {});
+        }
+    }
+
+    Ok(())
+}
+
+// check out if the allowance granted to this contract covers settling the order,
+// native assets are never moved by the contract and expired allowances count as none
+pub fn check_order_allowance(
+    querier: &QuerierWrapper,
+    api: &dyn Api,
+    env: &Env,
+    pair_info: &PairInfo,
+    order: &TraderRecord,
+) -> Result<(), ContractError> {
+    let (asset, amount) = if order.is_buy {
+        (
+            &pair_info.from_asset,
+            order.current_stock_amount * order.price / Uint128::from(NORMAL_DECIMAL),
+        )
+    } else {
+        (&pair_info.to_asset, order.current_stock_amount)
+    };
+
+    if let AssetInfo::Token { contract_addr } = asset {
+        let allowance = util::get_token_allowance(
+            *querier,
+            api.addr_validate(contract_addr.as_str())?,
+            order.address.clone(),
+            env.contract.address.clone(),
+            &env.block,
+        )?;
+        if allowance < amount {
+            return Err(ContractError::InsufficientAllowance {});
         }
+    }
 
-        SELLERS.remove(storage, key.clone());
+    Ok(())
+}
 
-        return Ok(MatchOrderResponse {
-            buyer: order.address.clone(),
-            seller: seller_record.address.clone(),
-            move_amount: move_amount,
-        });
+// Fills are settled by TransferFrom, so the order needs both the balance and the allowance.
+// On the ledger it needs its lock or enough free balance instead.
+pub fn check_order_funds(
+    storage: &dyn Storage,
+    querier: &QuerierWrapper,
+    api: &dyn Api,
+    env: &Env,
+    pair_info: &PairInfo,
+    order: &TraderRecord,
+) -> Result<(), ContractError> {
+    if CONFIG.load(storage)?.ledger {
+        return check_ledger_funds(storage, pair_info, order);
+    }
+    check_wallet_assets(pair_info)?;
+    check_order_balance(querier, api, pair_info, order)?;
+    check_order_allowance(querier, api, env, pair_info, order)
+}
+
+// Without the ledger fills are settled by moving tokens on behalf of the traders,
+// native coins can not be moved that way.
+pub fn check_wallet_assets(pair_info: &PairInfo) -> Result<(), ContractError> {
+    for asset in [&pair_info.from_asset, &pair_info.to_asset] {
+        if let AssetInfo::NativeToken { .. } = asset {
+            return Err(ContractError::UnacceptableToken {});
+        }
+    }
+    Ok(())
+}
+
+// A resting order that can no longer be settled is taken off the book instead of failing the batch.
+pub fn evict_order(
+    storage: &mut dyn Storage,
+    record: &TraderRecord,
+    reason: &ContractError,
+) -> StdResult<Event> {
+    cancel_resting_order(storage, record)?;
+    Ok(events::order_cancelled(record, "evicted").add_attribute("error", reason.to_string()))
+}
+
+pub fn get_pair_info(pair_list: &[PairInfo], pair_id: Uint128) -> Result<PairInfo, ContractError> {
+    match pair_list.get(pair_id.u128() as usize) {
+        Some(pair_info) => Ok(pair_info.clone()),
+        None => Err(ContractError::InvalidInput {}),
     }
 }
 
@@ -464,6 +1318,50 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
             start_after,
             limit,
         } => to_binary(&query_list_traders(deps, is_buy, start_after, limit)?),
+        QueryMsg::ListTriggers {
+            pair_id,
+            start_after,
+            limit,
+        } => to_binary(&query_list_triggers(deps, pair_id, start_after, limit)?),
+        QueryMsg::LastPrice { pair_id } => to_binary(&query_last_price(deps, pair_id)?),
+        QueryMsg::OrderGroup { group_id } => to_binary(&query_order_group(deps, group_id)?),
+        QueryMsg::Pair { pair_id } => to_binary(&query_pair(deps, pair_id)?),
+        QueryMsg::Balances { address } => to_binary(&query_balances(deps, address)?),
+        QueryMsg::Solvency {} => to_binary(&query_solvency(deps, _env)?),
+        QueryMsg::Claims { address } => to_binary(&query_claims(deps, address)?),
+        QueryMsg::SimulateRoute {
+            offer_asset,
+            ask_asset,
+            amount,
+            path,
+        } => to_binary(&query_simulate_route(
+            deps,
+            _env,
+            offer_asset,
+            ask_asset,
+            amount,
+            path,
+        )?),
+        QueryMsg::SimulateOrder {
+            pair_id,
+            side,
+            quantity,
+            price,
+        } => to_binary(&query_simulate_order(
+            deps, _env, pair_id, side, quantity, price,
+        )?),
+        QueryMsg::ReverseSimulate {
+            pair_id,
+            side,
+            desired_receive,
+        } => to_binary(&query_reverse_simulate(
+            deps,
+            _env,
+            pair_id,
+            side,
+            desired_receive,
+        )?),
+        QueryMsg::Hooks { address } => to_binary(&query_hooks(deps, address)?),
     }
 }
 
@@ -521,8 +1419,141 @@ fn query_list_traders(
     Ok(TraderListResponse { traders: stakers })
 }
 
+fn query_list_triggers(
+    deps: Deps,
+    pair_id: Uint128,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<TriggerListResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+    let start = start_after.map(|id| Bound::exclusive(id));
+
+    let triggers = TRIGGERS
+        .prefix(pair_key(pair_id))
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            item.map(|(id, trigger)| TriggerInfo {
+                id: String::from_utf8(id).unwrap(),
+                address: trigger.order.address,
+                is_buy: trigger.order.is_buy,
+                trigger_type: trigger.trigger_type,
+                trigger_price: trigger.trigger_price,
+                order_type: trigger.order_type,
+                trailing: trigger.trailing,
+                price: trigger.order.price,
+                current_stock_amount: trigger.order.current_stock_amount,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(TriggerListResponse { triggers })
+}
+
+fn query_last_price(deps: Deps, pair_id: Uint128) -> StdResult<LastPriceResponse> {
+    Ok(LastPriceResponse {
+        pair_id,
+        price: LAST_PRICES.may_load(deps.storage, pair_key(pair_id))?,
+    })
+}
+
+fn query_order_group(deps: Deps, group_id: String) -> StdResult<OrderGroupResponse> {
+    let group = ORDER_GROUPS.load(deps.storage, group_id.clone())?;
+    Ok(OrderGroupResponse {
+        group_id,
+        address: group.address,
+        pair_id: group.pair_id,
+        is_buy: group.is_buy,
+        limit_id: group.limit_id,
+        stop_id: group.stop_id,
+        entry_id: group.entry_id,
+        take_profit_price: group.take_profit_price,
+        stop_price: group.stop_price,
+    })
+}
+
+// Records indexed by the migration per call, the rest is left to MigrateIndex.
+const MIGRATE_LIMIT: u32 = 100;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> StdResult<Response> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
-    Ok(Response::default())
+
+    INDEX_MIGRATION.save(
+        deps.storage,
+        &IndexMigration {
+            stage: 0,
+            start_after: None,
+        },
+    )?;
+    let done = migrate_index(deps.storage, MIGRATE_LIMIT as usize)?;
+    Ok(Response::new().add_attribute("done", done.to_string()))
+}
+
+pub fn execute_migrate_index(deps: DepsMut, limit: Option<u32>) -> Result<Response, ContractError> {
+    if INDEX_MIGRATION.may_load(deps.storage)?.is_none() {
+        return Err(ContractError::InvalidInput {});
+    }
+    let limit = limit.unwrap_or(MIGRATE_LIMIT).min(MIGRATE_LIMIT) as usize;
+    let done = migrate_index(deps.storage, limit)?;
+    Ok(Response::new()
+        .add_attribute("action", "migrate_index")
+        .add_attribute("done", done.to_string()))
+}
+
+// Indexes up to `limit` orders placed before the book was indexed, then triggers placed
+// before they were, filed again in their current order. Returns whether all are indexed.
+fn migrate_index(storage: &mut dyn Storage, limit: usize) -> StdResult<bool> {
+    let mut migration = INDEX_MIGRATION.load(storage)?;
+    let mut left = limit;
+    while migration.stage < 3 && left > 0 {
+        let start = migration.start_after.clone().map(Bound::exclusive);
+        let indexed = if migration.stage < 2 {
+            let book = if migration.stage == 0 {
+                &BUYERS
+            } else {
+                &SELLERS
+            };
+            let records = book
+                .range(storage, start, None, Order::Ascending)
+                .take(left)
+                .collect::<StdResult<Vec<(Vec<u8>, TraderRecord)>>>()?;
+            for (_, record) in records.iter() {
+                let priority_key = (side_key(record.is_buy), record.id.clone());
+                match ORDER_PRIORITY.may_load(storage, priority_key)? {
+                    Some(priority) => index_order(storage, record, priority)?,
+                    None => assign_priority(storage, record)?,
+                }
+            }
+            records.into_iter().map(|(key, _)| key).collect::<Vec<_>>()
+        } else {
+            let triggers = TRIGGERS
+                .range(storage, start, None, Order::Ascending)
+                .take(left)
+                .collect::<StdResult<Vec<(Vec<u8>, TriggerOrder)>>>()?;
+            let mut keys = vec![];
+            for (key, trigger) in triggers {
+                remove_trigger(storage, &trigger);
+                add_trigger(storage, trigger)?;
+                keys.push(key);
+            }
+            keys
+        };
+
+        if indexed.len() < left {
+            migration.stage += 1;
+            migration.start_after = None;
+        } else {
+            migration.start_after = indexed.last().cloned();
+        }
+        left -= indexed.len();
+    }
+
+    if migration.stage < 3 {
+        INDEX_MIGRATION.save(storage, &migration)?;
+        return Ok(false);
+    }
+    INDEX_MIGRATION.remove(storage);
+    Ok(true)
 }
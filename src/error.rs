@@ -1,4 +1,4 @@
-use cosmwasm_std::{OverflowError, StdError};
+use cosmwasm_std::{OverflowError, StdError, Uint128};
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -24,6 +24,12 @@ pub enum ContractError {
     #[error("Not enough Reward")]
     NotEnoughReward {},
 
+    #[error("Allowance is not enough")]
+    InsufficientAllowance {},
+
+    #[error("Free balance is not enough")]
+    InsufficientFunds {},
+
     #[error("Not reward token")]
     NotRewardToken {},
 
@@ -56,4 +62,28 @@ pub enum ContractError {
 
     #[error("Not Same Price")]
     NotSamePrice {},
+
+    #[error("Pair is not available for this action")]
+    PairUnavailable {},
+
+    #[error("Price is out of the allowed band")]
+    PriceOutOfBand {},
+
+    #[error("Not enough liquidity in the book")]
+    NotEnoughLiquidity {},
+
+    #[error("Receive amount {receive} is below the minimum")]
+    MinReceiveNotReached { receive: Uint128 },
+
+    #[error("Hook is already registered")]
+    HookAlreadyRegistered {},
+
+    #[error("Hook is not registered")]
+    HookNotRegistered {},
+
+    #[error("Too many hooks, at most {max}")]
+    TooManyHooks { max: u32 },
+
+    #[error("Circuit breaker halted the pair, please try after {until}")]
+    CircuitBreakerHalted { until: u64 },
 }
@@ -0,0 +1,150 @@
+use cosmwasm_std::{Addr, Event, Uint128};
+
+use crate::msg::TradeHookMsg;
+use crate::state::PairSettings;
+
+use classic_terrapexc::trading::TraderRecord;
+
+// bumped whenever an event type or attribute name changes
+pub const EVENT_SCHEMA_VERSION: &str = "1";
+
+fn event(ty: &str) -> Event {
+    Event::new(ty).add_attribute("schema_version", EVENT_SCHEMA_VERSION)
+}
+
+fn side(is_buy: bool) -> &'static str {
+    if is_buy {
+        "buy"
+    } else {
+        "sell"
+    }
+}
+
+// `kind` tells where the order rests: book, iceberg or trigger.
+// Icebergs only ever show their visible slice, every refilled slice is placed again.
+pub fn order_placed(record: &TraderRecord, kind: &str) -> Event {
+    event("order_placed")
+        .add_attribute("order_id", record.id.clone())
+        .add_attribute("address", record.address.clone())
+        .add_attribute("pair_id", record.pair_id)
+        .add_attribute("side", side(record.is_buy))
+        .add_attribute("price", record.price)
+        .add_attribute("quantity", record.current_stock_amount)
+        .add_attribute("kind", kind)
+}
+
+// The order's price or remaining quantity changed in place.
+pub fn order_amended(record: &TraderRecord) -> Event {
+    event("order_amended")
+        .add_attribute("order_id", record.id.clone())
+        .add_attribute("address", record.address.clone())
+        .add_attribute("pair_id", record.pair_id)
+        .add_attribute("side", side(record.is_buy))
+        .add_attribute("price", record.price)
+        .add_attribute("quantity", record.current_stock_amount)
+}
+
+pub struct Fill<'a> {
+    pub pair_id: Uint128,
+    pub maker_order_id: &'a str,
+    pub maker: &'a Addr,
+    pub taker_order_id: &'a str,
+    pub taker: &'a Addr,
+    pub taker_is_buy: bool,
+    pub price: Uint128,
+    pub quantity: Uint128,
+}
+
+// No fees are charged yet, both fee attributes are zero.
+pub fn order_filled(fill: &Fill) -> Event {
+    event("order_filled")
+        .add_attribute("pair_id", fill.pair_id)
+        .add_attribute("maker_order_id", fill.maker_order_id)
+        .add_attribute("maker", fill.maker.clone())
+        .add_attribute("taker_order_id", fill.taker_order_id)
+        .add_attribute("taker", fill.taker.clone())
+        .add_attribute("taker_side", side(fill.taker_is_buy))
+        .add_attribute("price", fill.price)
+        .add_attribute("quantity", fill.quantity)
+        .add_attribute("maker_fee", Uint128::zero())
+        .add_attribute("taker_fee", Uint128::zero())
+}
+
+// `reason` is one of user, forced, self_trade or evicted
+pub fn order_cancelled(record: &TraderRecord, reason: &str) -> Event {
+    event("order_cancelled")
+        .add_attribute("order_id", record.id.clone())
+        .add_attribute("address", record.address.clone())
+        .add_attribute("pair_id", record.pair_id)
+        .add_attribute("side", side(record.is_buy))
+        .add_attribute("quantity", record.current_stock_amount)
+        .add_attribute("reason", reason)
+}
+
+pub fn pair_updated(pair_id: Uint128, settings: &PairSettings) -> Event {
+    event("pair_updated")
+        .add_attribute("pair_id", pair_id)
+        .add_attribute("status", format!("{:?}", settings.status))
+        .add_attribute(
+            "self_trade_prevention",
+            format!("{:?}", settings.self_trade_prevention),
+        )
+        .add_attribute(
+            "token_transfer_mode",
+            format!("{:?}", settings.token_transfer_mode),
+        )
+        .add_attribute("price_band", settings.price_band.is_some().to_string())
+        .add_attribute(
+            "circuit_breaker",
+            settings.circuit_breaker.is_some().to_string(),
+        )
+}
+
+// Notifications for the hooks of the traders an event concerns,
+// a fill notifies both the maker and the taker.
+pub fn hook_msgs(event: &Event) -> Vec<TradeHookMsg> {
+    let text = |key: &str| {
+        event
+            .attributes
+            .iter()
+            .find(|attr| attr.key == key)
+            .map(|attr| attr.value.clone())
+            .unwrap_or_default()
+    };
+    let amount = |key: &str| Uint128::from(text(key).parse::<u128>().unwrap_or_default());
+
+    match event.ty.as_str() {
+        "order_filled" => {
+            let taker_is_buy = text("taker_side") == "buy";
+            vec![
+                TradeHookMsg::OrderFilled {
+                    pair_id: amount("pair_id"),
+                    order_id: text("maker_order_id"),
+                    address: Addr::unchecked(text("maker")),
+                    is_buy: !taker_is_buy,
+                    is_maker: true,
+                    price: amount("price"),
+                    quantity: amount("quantity"),
+                },
+                TradeHookMsg::OrderFilled {
+                    pair_id: amount("pair_id"),
+                    order_id: text("taker_order_id"),
+                    address: Addr::unchecked(text("taker")),
+                    is_buy: taker_is_buy,
+                    is_maker: false,
+                    price: amount("price"),
+                    quantity: amount("quantity"),
+                },
+            ]
+        }
+        "order_cancelled" => vec![TradeHookMsg::OrderCancelled {
+            pair_id: amount("pair_id"),
+            order_id: text("order_id"),
+            address: Addr::unchecked(text("address")),
+            is_buy: text("side") == "buy",
+            quantity: amount("quantity"),
+            reason: text("reason"),
+        }],
+        _ => vec![],
+    }
+}
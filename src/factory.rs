@@ -0,0 +1,105 @@
+use cosmwasm_std::{
+    attr, to_binary, DepsMut, Env, MessageInfo, Reply, Response, StdError, SubMsg, Uint128, WasmMsg,
+};
+use protobuf::Message;
+
+use crate::contract::check_owner;
+use crate::error::ContractError;
+use crate::ledger::asset_key;
+use crate::msg::ExecuteMsg;
+use crate::response::MsgInstantiateContractResponse;
+use crate::state::{pair_key, CONFIG, PAIR_CONTRACTS, PENDING_PAIR};
+
+use classic_terrapexc::trading::{InstantiateMsg, PairInfo};
+
+pub const INSTANTIATE_PAIR_REPLY_ID: u64 = 2;
+
+//////////////////////////////////////////////////
+// Description:  Only owner can execute it, lists a new pair. In factory mode the pair
+//               gets its own orderbook contract, instantiated from `pair_code_id`
+// Params: [1] - Pair to list
+/////////////////////////////////////////////////
+pub fn execute_add_pair(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    pair_info: PairInfo,
+) -> Result<Response, ContractError> {
+    check_owner(&deps, &info)?;
+    let mut cfg = CONFIG.load(deps.storage)?;
+
+    let listed = cfg.pair_list.iter().any(|listed| {
+        asset_key(&listed.from_asset) == asset_key(&pair_info.from_asset)
+            && asset_key(&listed.to_asset) == asset_key(&pair_info.to_asset)
+    });
+    if listed {
+        return Err(ContractError::InvalidInput {});
+    }
+
+    // the pair keeps its id here, orders for it are placed on its own contract as pair 0
+    let pair_id = Uint128::from(cfg.pair_list.len() as u128);
+    cfg.pair_list.push(pair_info.clone());
+    CONFIG.save(deps.storage, &cfg)?;
+
+    let mut res =
+        Response::new().add_attributes(vec![attr("action", "add_pair"), attr("pair_id", pair_id)]);
+    if let Some(code_id) = cfg.pair_code_id {
+        PENDING_PAIR.save(deps.storage, &pair_id)?;
+        let instantiate = WasmMsg::Instantiate {
+            admin: Some(deps.api.addr_humanize(&cfg.owner)?.to_string()),
+            code_id,
+            msg: to_binary(&InstantiateMsg {
+                pair_list: vec![pair_info],
+                enabled: cfg.enabled,
+            })?,
+            funds: vec![],
+            label: format!("{} pair {}", env.contract.address, pair_id),
+        };
+        res = res.add_submessage(SubMsg::reply_on_success(
+            instantiate,
+            INSTANTIATE_PAIR_REPLY_ID,
+        ));
+    }
+
+    Ok(res)
+}
+
+// Reply of a pair contract instantiation, registers the new contract for the pending pair
+// and hands its ownership over to the owner of this contract.
+pub fn pair_instantiated(deps: DepsMut, msg: Reply) -> Result<Response, ContractError> {
+    let data = msg
+        .result
+        .into_result()
+        .map_err(StdError::generic_err)?
+        .data
+        .ok_or_else(|| StdError::generic_err("missing instantiate response data"))?;
+    let res: MsgInstantiateContractResponse =
+        Message::parse_from_bytes(data.as_slice()).map_err(|_| {
+            StdError::parse_err("MsgInstantiateContractResponse", "failed to parse data")
+        })?;
+    let contract = deps.api.addr_validate(res.get_contract_address())?;
+
+    let pair_id = PENDING_PAIR.load(deps.storage)?;
+    PENDING_PAIR.remove(deps.storage);
+    PAIR_CONTRACTS.save(deps.storage, pair_key(pair_id), &contract)?;
+
+    let cfg = CONFIG.load(deps.storage)?;
+    let handover = WasmMsg::Execute {
+        contract_addr: contract.to_string(),
+        msg: to_binary(&ExecuteMsg::UpdateConfig {
+            owner: Some(deps.api.addr_humanize(&cfg.owner)?.to_string()),
+            pair_list: None,
+            enabled: None,
+            ledger: None,
+            treasury: None,
+            pair_code_id: None,
+        })?,
+        funds: vec![],
+    };
+
+    Ok(Response::new().add_message(handover).add_attributes(vec![
+        attr("action", "pair_instantiated"),
+        attr("pair_id", pair_id),
+        attr("contract", contract),
+    ]))
+}
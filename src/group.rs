@@ -0,0 +1,405 @@
+use cosmwasm_std::{attr, DepsMut, Env, MessageInfo, Response, StdResult, Storage, Uint128};
+
+use crate::contract::{
+    add_orderbook, assign_priority, check_enabled, check_order_funds, get_pair_info,
+    remove_from_orderbook,
+};
+use crate::error::ContractError;
+use crate::events;
+use crate::ledger::ledger_enabled;
+use crate::msg::{OrderType, TriggerType};
+use crate::pair::{check_circuit_breaker, check_pair_status, check_price_band, PairAction};
+use crate::state::{
+    pair_key, OrderGroup, TriggerOrder, BUYERS, CONFIG, GROUP_OF, ORDER_GROUPS, PENDING_EXIT_LOCKS,
+    SELLERS, TRIGGERS,
+};
+use crate::trigger::{add_trigger, remove_trigger};
+
+use classic_terrapexc::trading::TraderRecord;
+
+//////////////////////////////////////////////////
+// Description:  Place a take profit limit and a stop loss that cancel each other
+// Params: [1] - Limit order resting in the book
+//         [2] - Stop order resting in the trigger book
+//         [3] - Last traded price that fires the stop
+//         [4] - Market or limit order once the stop fires
+/////////////////////////////////////////////////
+pub fn execute_place_oco(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    limit_order: TraderRecord,
+    stop_order: TraderRecord,
+    stop_price: Uint128,
+    stop_order_type: OrderType,
+) -> Result<Response, ContractError> {
+    check_enabled(&deps, &info)?;
+    let cfg = CONFIG.load(deps.storage)?;
+    let pair_info = get_pair_info(&cfg.pair_list, limit_order.pair_id)?;
+    check_pair_status(deps.storage, limit_order.pair_id, PairAction::Place)?;
+    check_circuit_breaker(deps.storage, env.block.time.seconds(), limit_order.pair_id)?;
+
+    if limit_order.address != info.sender || stop_order.address != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if limit_order.pair_id != stop_order.pair_id
+        || limit_order.is_buy != stop_order.is_buy
+        || limit_order.id == stop_order.id
+    {
+        return Err(ContractError::InvalidInput {});
+    }
+    if limit_order.current_stock_amount.is_zero()
+        || stop_order.current_stock_amount.is_zero()
+        || limit_order.price.is_zero()
+        || stop_price.is_zero()
+    {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+    check_price_band(deps.storage, limit_order.pair_id, limit_order.price)?;
+    if stop_order_type == OrderType::Limit {
+        check_price_band(deps.storage, stop_order.pair_id, stop_order.price)?;
+    }
+    check_order_funds(
+        deps.storage,
+        &deps.querier,
+        deps.api,
+        &env,
+        &pair_info,
+        &limit_order,
+    )?;
+    check_order_funds(
+        deps.storage,
+        &deps.querier,
+        deps.api,
+        &env,
+        &pair_info,
+        &stop_order,
+    )?;
+
+    let group = OrderGroup {
+        address: info.sender.clone(),
+        pair_id: limit_order.pair_id,
+        is_buy: limit_order.is_buy,
+        limit_id: limit_order.id.clone(),
+        stop_id: stop_order.id.clone(),
+        entry_id: None,
+        take_profit_price: limit_order.price,
+        stop_price,
+        stop_order_type,
+    };
+    check_leg_ids(deps.storage, &group)?;
+    save_group(deps.storage, &limit_order.id, &group)?;
+
+    add_orderbook(deps.storage, limit_order.clone())?;
+    let stop_placed = events::order_placed(&stop_order, "trigger");
+    save_stop_leg(deps.storage, &group, stop_order)?;
+
+    Ok(Response::new()
+        .add_event(events::order_placed(&limit_order, "book"))
+        .add_event(stop_placed)
+        .add_attributes(vec![
+            attr("action", "place_oco"),
+            attr("address", info.sender),
+            attr("group_id", limit_order.id),
+        ]))
+}
+
+//////////////////////////////////////////////////
+// Description:  Place an entry order whose fills spawn an OCO exit pair
+// Params: [1] - Entry order resting in the book
+//         [2] - Limit price of the take profit leg
+//         [3] - Last traded price that fires the stop leg
+//         [4] - Market or limit order once the stop fires
+/////////////////////////////////////////////////
+pub fn execute_place_bracket(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    entry: TraderRecord,
+    take_profit_price: Uint128,
+    stop_price: Uint128,
+    stop_order_type: OrderType,
+) -> Result<Response, ContractError> {
+    check_enabled(&deps, &info)?;
+    let cfg = CONFIG.load(deps.storage)?;
+    let pair_info = get_pair_info(&cfg.pair_list, entry.pair_id)?;
+    check_pair_status(deps.storage, entry.pair_id, PairAction::Place)?;
+    check_circuit_breaker(deps.storage, env.block.time.seconds(), entry.pair_id)?;
+
+    if entry.address != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if entry.current_stock_amount.is_zero()
+        || entry.price.is_zero()
+        || take_profit_price.is_zero()
+        || stop_price.is_zero()
+    {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+    // the take profit closes the position at a gain, so the entry's proceeds pay for it
+    if entry.is_buy && take_profit_price <= entry.price
+        || !entry.is_buy && take_profit_price >= entry.price
+    {
+        return Err(ContractError::InvalidInput {});
+    }
+    check_price_band(deps.storage, entry.pair_id, entry.price)?;
+    check_price_band(deps.storage, entry.pair_id, take_profit_price)?;
+    check_order_funds(
+        deps.storage,
+        &deps.querier,
+        deps.api,
+        &env,
+        &pair_info,
+        &entry,
+    )?;
+
+    // the exit legs close the position opened by the entry
+    let group = OrderGroup {
+        address: info.sender.clone(),
+        pair_id: entry.pair_id,
+        is_buy: !entry.is_buy,
+        limit_id: format!("{}-tp", entry.id),
+        stop_id: format!("{}-sl", entry.id),
+        entry_id: Some(entry.id.clone()),
+        take_profit_price,
+        stop_price,
+        stop_order_type,
+    };
+    check_leg_ids(deps.storage, &group)?;
+    save_group(deps.storage, &entry.id, &group)?;
+
+    add_orderbook(deps.storage, entry.clone())?;
+
+    Ok(Response::new()
+        .add_event(events::order_placed(&entry, "book"))
+        .add_attributes(vec![
+            attr("action", "place_bracket"),
+            attr("address", info.sender),
+            attr("group_id", entry.id),
+        ]))
+}
+
+// Ids of grouped orders are reserved for their group, no other order can take them.
+pub fn check_group_id(storage: &dyn Storage, id: &str) -> Result<(), ContractError> {
+    if GROUP_OF.has(storage, id.to_string()) {
+        return Err(ContractError::AlreadyStarted {});
+    }
+    Ok(())
+}
+
+// The exit legs can not take over orders already resting under their ids.
+fn check_leg_ids(storage: &dyn Storage, group: &OrderGroup) -> Result<(), ContractError> {
+    let book = if group.is_buy { &BUYERS } else { &SELLERS };
+    if book.has(storage, group.limit_id.clone())
+        || TRIGGERS.has(storage, (pair_key(group.pair_id), group.stop_id.clone()))
+    {
+        return Err(ContractError::AlreadyStarted {});
+    }
+    Ok(())
+}
+
+fn save_group(
+    storage: &mut dyn Storage,
+    group_id: &str,
+    group: &OrderGroup,
+) -> Result<(), ContractError> {
+    let mut member_ids = vec![group.limit_id.clone(), group.stop_id.clone()];
+    if let Some(entry_id) = group.entry_id.clone() {
+        member_ids.push(entry_id);
+    }
+    for member_id in member_ids {
+        if GROUP_OF.has(storage, member_id.clone()) {
+            return Err(ContractError::AlreadyStarted {});
+        }
+        GROUP_OF.save(storage, member_id, &group_id.to_string())?;
+    }
+    ORDER_GROUPS.save(storage, group_id.to_string(), group)?;
+    Ok(())
+}
+
+fn save_stop_leg(
+    storage: &mut dyn Storage,
+    group: &OrderGroup,
+    order: TraderRecord,
+) -> StdResult<()> {
+    add_trigger(
+        storage,
+        TriggerOrder {
+            order,
+            trigger_type: TriggerType::StopLoss,
+            trigger_price: group.stop_price,
+            order_type: group.stop_order_type,
+            trailing: None,
+            sequence: 0,
+        },
+    )
+}
+
+// Loads the group `record` belongs to, None for ungrouped orders or id clashes with another
+// owner, side or pair.
+fn load_group(
+    storage: &dyn Storage,
+    record: &TraderRecord,
+) -> StdResult<Option<(String, OrderGroup)>> {
+    let group_id = match GROUP_OF.may_load(storage, record.id.clone())? {
+        Some(group_id) => group_id,
+        None => return Ok(None),
+    };
+    let group = ORDER_GROUPS.load(storage, group_id.clone())?;
+    let is_entry = group.entry_id.as_ref() == Some(&record.id);
+    if group.address != record.address
+        || group.pair_id != record.pair_id
+        || (record.is_buy == group.is_buy) == is_entry
+    {
+        return Ok(None);
+    }
+    Ok(Some((group_id, group)))
+}
+
+// Drops the group once the entry is gone and neither exit leg rests anymore.
+fn cleanup_group(storage: &mut dyn Storage, group_id: String, group: &OrderGroup) -> StdResult<()> {
+    let book = if group.is_buy { &BUYERS } else { &SELLERS };
+    let limit_rests = book.has(storage, group.limit_id.clone());
+    let stop_rests = TRIGGERS.has(storage, (pair_key(group.pair_id), group.stop_id.clone()));
+
+    if group.entry_id.is_some() || limit_rests || stop_rests {
+        return ORDER_GROUPS.save(storage, group_id, group);
+    }
+    GROUP_OF.remove(storage, group.limit_id.clone());
+    GROUP_OF.remove(storage, group.stop_id.clone());
+    ORDER_GROUPS.remove(storage, group_id);
+    Ok(())
+}
+
+// Called for every fill of a resting book order.
+// A filled limit leg reduces its stop, a filled bracket entry spawns or grows the exit pair.
+pub fn on_order_filled(
+    storage: &mut dyn Storage,
+    record: &TraderRecord,
+    fill_amount: Uint128,
+    fully_filled: bool,
+) -> StdResult<()> {
+    let (group_id, mut group) = match load_group(storage, record)? {
+        Some(found) => found,
+        None => return Ok(()),
+    };
+
+    if group.entry_id.as_ref() == Some(&record.id) {
+        grow_exit_legs(storage, &group, fill_amount)?;
+        if fully_filled {
+            GROUP_OF.remove(storage, record.id.clone());
+            group.entry_id = None;
+        }
+        return cleanup_group(storage, group_id, &group);
+    }
+
+    if record.id == group.limit_id {
+        let stop_key = (pair_key(group.pair_id), group.stop_id.clone());
+        if let Some(mut stop) = TRIGGERS.may_load(storage, stop_key.clone())? {
+            stop.order.current_stock_amount =
+                stop.order.current_stock_amount.saturating_sub(fill_amount);
+            if fully_filled || stop.order.current_stock_amount.is_zero() {
+                remove_trigger(storage, &stop);
+            } else {
+                TRIGGERS.save(storage, stop_key, &stop)?;
+            }
+        }
+        return cleanup_group(storage, group_id, &group);
+    }
+
+    Ok(())
+}
+
+// On the ledger the limit leg is locked once the entry fill settled and paid for it,
+// see ledger::settle_ledger. Both legs are checked like any other order when they match.
+// Records of another owner under a leg's id are never grown.
+fn grow_exit_legs(storage: &mut dyn Storage, group: &OrderGroup, amount: Uint128) -> StdResult<()> {
+    let book = if group.is_buy { &BUYERS } else { &SELLERS };
+    let limit_rests = book.has(storage, group.limit_id.clone());
+    let mut limit_leg = match book.may_load(storage, group.limit_id.clone())? {
+        Some(record) if record.address != group.address => return Ok(()),
+        Some(record) => record,
+        None => TraderRecord {
+            id: group.limit_id.clone(),
+            address: group.address.clone(),
+            pair_id: group.pair_id,
+            is_buy: group.is_buy,
+            price: group.take_profit_price,
+            order_stock_amount: Uint128::zero(),
+            current_stock_amount: Uint128::zero(),
+        },
+    };
+    let stop_key = (pair_key(group.pair_id), group.stop_id.clone());
+    let stop_leg = TRIGGERS.may_load(storage, stop_key.clone())?;
+    if let Some(stop) = stop_leg.as_ref() {
+        if stop.order.address != group.address {
+            return Ok(());
+        }
+    }
+
+    limit_leg.order_stock_amount += amount;
+    limit_leg.current_stock_amount += amount;
+    book.save(storage, group.limit_id.clone(), &limit_leg)?;
+    if !limit_rests {
+        assign_priority(storage, &limit_leg)?;
+    }
+    if ledger_enabled(storage)? {
+        let pending_key = (group.address.clone(), group.limit_id.clone());
+        let pending = PENDING_EXIT_LOCKS
+            .may_load(storage, pending_key.clone())?
+            .unwrap_or_default();
+        PENDING_EXIT_LOCKS.save(storage, pending_key, &(pending + amount))?;
+    }
+
+    match stop_leg {
+        Some(mut stop) => {
+            stop.order.order_stock_amount += amount;
+            stop.order.current_stock_amount += amount;
+            TRIGGERS.save(storage, stop_key, &stop)
+        }
+        None => save_stop_leg(
+            storage,
+            group,
+            TraderRecord {
+                id: group.stop_id.clone(),
+                price: group.stop_price,
+                ..limit_leg
+            },
+        ),
+    }
+}
+
+// Called when a stop leg fires, the limit leg on the other side of the OCO is cancelled.
+pub fn on_trigger_fired(storage: &mut dyn Storage, record: &TraderRecord) -> StdResult<()> {
+    let (group_id, group) = match load_group(storage, record)? {
+        Some(found) => found,
+        None => return Ok(()),
+    };
+    if record.id != group.stop_id {
+        return Ok(());
+    }
+
+    remove_from_orderbook(storage, group.is_buy, group.limit_id.clone())?;
+    cleanup_group(storage, group_id, &group)
+}
+
+// Called when a grouped order is cancelled by its owner.
+// Cancelling either exit leg cancels the other one, cancelling the entry stops new legs from spawning.
+pub fn on_order_cancelled(storage: &mut dyn Storage, record: &TraderRecord) -> StdResult<()> {
+    let (group_id, mut group) = match load_group(storage, record)? {
+        Some(found) => found,
+        None => return Ok(()),
+    };
+
+    if group.entry_id.as_ref() == Some(&record.id) {
+        GROUP_OF.remove(storage, record.id.clone());
+        group.entry_id = None;
+    } else {
+        remove_from_orderbook(storage, group.is_buy, group.limit_id.clone())?;
+        let stop_key = (pair_key(group.pair_id), group.stop_id.clone());
+        if let Some(stop) = TRIGGERS.may_load(storage, stop_key)? {
+            remove_trigger(storage, &stop);
+        }
+    }
+    cleanup_group(storage, group_id, &group)
+}
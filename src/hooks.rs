@@ -0,0 +1,233 @@
+use cosmwasm_std::{
+    attr, to_binary, Addr, Deps, DepsMut, MessageInfo, Reply, Response, StdResult, Storage, SubMsg,
+    WasmMsg,
+};
+
+use crate::contract::check_owner;
+use crate::error::ContractError;
+use crate::events;
+use crate::msg::{HooksResponse, TradeHookExecuteMsg, TradeHookMsg};
+use crate::state::{GLOBAL_HOOKS, TRADER_HOOKS};
+
+pub const HOOK_REPLY_ID: u64 = 1;
+// a hook runs with its own gas budget so it can not make fills run out of gas
+pub const HOOK_GAS_LIMIT: u64 = 200_000;
+// gas the hook callbacks of a transaction may use together, beyond the first hook of each trader
+pub const MAX_HOOK_GAS: u64 = 1_000_000;
+pub const MAX_HOOKS: u32 = 5;
+
+//////////////////////////////////////////////////
+// Description:  Register a hook contract for the sender's orders
+// Params: [1] - Hook contract
+/////////////////////////////////////////////////
+pub fn execute_add_hook(
+    deps: DepsMut,
+    info: MessageInfo,
+    addr: String,
+) -> Result<Response, ContractError> {
+    let hook = deps.api.addr_validate(&addr)?;
+    let mut hooks = TRADER_HOOKS
+        .may_load(deps.storage, info.sender.clone())?
+        .unwrap_or_default();
+    add_hook(&mut hooks, hook)?;
+    TRADER_HOOKS.save(deps.storage, info.sender.clone(), &hooks)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "add_hook"),
+        attr("address", info.sender),
+        attr("hook", addr),
+    ]))
+}
+
+pub fn execute_remove_hook(
+    deps: DepsMut,
+    info: MessageInfo,
+    addr: String,
+) -> Result<Response, ContractError> {
+    let hook = deps.api.addr_validate(&addr)?;
+    let mut hooks = TRADER_HOOKS
+        .may_load(deps.storage, info.sender.clone())?
+        .unwrap_or_default();
+    remove_hook(&mut hooks, &hook)?;
+    if hooks.is_empty() {
+        TRADER_HOOKS.remove(deps.storage, info.sender.clone());
+    } else {
+        TRADER_HOOKS.save(deps.storage, info.sender.clone(), &hooks)?;
+    }
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "remove_hook"),
+        attr("address", info.sender),
+        attr("hook", addr),
+    ]))
+}
+
+//////////////////////////////////////////////////
+// Description:  Only owner can execute it, register a hook for the orders of every trader
+// Params: [1] - Hook contract
+/////////////////////////////////////////////////
+pub fn execute_add_global_hook(
+    deps: DepsMut,
+    info: MessageInfo,
+    addr: String,
+) -> Result<Response, ContractError> {
+    check_owner(&deps, &info)?;
+    let hook = deps.api.addr_validate(&addr)?;
+    let mut hooks = GLOBAL_HOOKS.may_load(deps.storage)?.unwrap_or_default();
+    add_hook(&mut hooks, hook)?;
+    GLOBAL_HOOKS.save(deps.storage, &hooks)?;
+
+    Ok(Response::new().add_attributes(vec![attr("action", "add_global_hook"), attr("hook", addr)]))
+}
+
+pub fn execute_remove_global_hook(
+    deps: DepsMut,
+    info: MessageInfo,
+    addr: String,
+) -> Result<Response, ContractError> {
+    check_owner(&deps, &info)?;
+    let hook = deps.api.addr_validate(&addr)?;
+    let mut hooks = GLOBAL_HOOKS.may_load(deps.storage)?.unwrap_or_default();
+    remove_hook(&mut hooks, &hook)?;
+    GLOBAL_HOOKS.save(deps.storage, &hooks)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "remove_global_hook"),
+        attr("hook", addr),
+    ]))
+}
+
+fn add_hook(hooks: &mut Vec<Addr>, hook: Addr) -> Result<(), ContractError> {
+    if hooks.contains(&hook) {
+        return Err(ContractError::HookAlreadyRegistered {});
+    }
+    if hooks.len() >= MAX_HOOKS as usize {
+        return Err(ContractError::TooManyHooks { max: MAX_HOOKS });
+    }
+    hooks.push(hook);
+    Ok(())
+}
+
+fn remove_hook(hooks: &mut Vec<Addr>, hook: &Addr) -> Result<(), ContractError> {
+    match hooks.iter().position(|registered| registered == hook) {
+        Some(index) => {
+            hooks.remove(index);
+            Ok(())
+        }
+        None => Err(ContractError::HookNotRegistered {}),
+    }
+}
+
+// Appends one callback per hook with the fill and cancel notifications of `res` it is
+// interested in. Callbacks reply on error so a failing hook can not revert the orders it is
+// notified about. The traders notified, and the global hooks as one more, take turns:
+// the first hook of each is always called, further ones in rounds while MAX_HOOK_GAS lasts,
+// so no trader's hooks can crowd out another's. Hooks left out are reported in `hooks_skipped`.
+pub fn notify_hooks(storage: &dyn Storage, res: Response) -> StdResult<Response> {
+    let global = GLOBAL_HOOKS.may_load(storage)?.unwrap_or_default();
+
+    // (hook, notifications) and the hooks of every party, in the order they are first seen
+    let mut batches: Vec<(Addr, Vec<TradeHookMsg>)> = vec![];
+    let mut parties: Vec<(Option<Addr>, Vec<Addr>)> = vec![];
+    for event in res.events.iter() {
+        for hook_msg in events::hook_msgs(event) {
+            if parties.is_empty() && !global.is_empty() {
+                parties.push((None, global.clone()));
+            }
+            let address = hook_msg.address().clone();
+            let trader_hooks = TRADER_HOOKS
+                .may_load(storage, address.clone())?
+                .unwrap_or_default();
+            if !trader_hooks.is_empty()
+                && !parties
+                    .iter()
+                    .any(|(party, _)| party.as_ref() == Some(&address))
+            {
+                parties.push((Some(address), trader_hooks.clone()));
+            }
+
+            let mut hooks = global.clone();
+            for hook in trader_hooks {
+                if !hooks.contains(&hook) {
+                    hooks.push(hook);
+                }
+            }
+            for hook in hooks {
+                match batches.iter_mut().find(|(batched, _)| *batched == hook) {
+                    Some((_, notifications)) => notifications.push(hook_msg.clone()),
+                    None => batches.push((hook, vec![hook_msg.clone()])),
+                }
+            }
+        }
+    }
+
+    let mut called: Vec<Addr> = vec![];
+    let rounds = parties
+        .iter()
+        .map(|(_, hooks)| hooks.len())
+        .max()
+        .unwrap_or(0);
+    for round in 0..rounds {
+        for (_, hooks) in parties.iter() {
+            let hook = match hooks.get(round) {
+                Some(hook) if !called.contains(hook) => hook,
+                _ => continue,
+            };
+            if round > 0 && (called.len() as u64 + 1) * HOOK_GAS_LIMIT > MAX_HOOK_GAS {
+                continue;
+            }
+            called.push(hook.clone());
+        }
+    }
+
+    let mut messages: Vec<SubMsg> = vec![];
+    for hook in called.iter() {
+        let notifications = match batches.iter().find(|(batched, _)| batched == hook) {
+            Some((_, notifications)) => notifications.clone(),
+            None => continue,
+        };
+        let execute = WasmMsg::Execute {
+            contract_addr: hook.to_string(),
+            msg: to_binary(&TradeHookExecuteMsg::TradeHook(notifications))?,
+            funds: vec![],
+        };
+        let mut sub_msg = SubMsg::reply_on_error(execute, HOOK_REPLY_ID);
+        sub_msg.gas_limit = Some(HOOK_GAS_LIMIT);
+        messages.push(sub_msg);
+    }
+    let skipped: Vec<String> = batches
+        .iter()
+        .filter(|(hook, _)| !called.contains(hook))
+        .map(|(hook, _)| hook.to_string())
+        .collect();
+
+    let res = res.add_submessages(messages);
+    if skipped.is_empty() {
+        return Ok(res);
+    }
+    Ok(res.add_attribute("hooks_skipped", skipped.join(",")))
+}
+
+// Reply of a failed hook callback, the error is only reported.
+pub fn hook_failed(msg: Reply) -> Response {
+    let error = msg.result.into_result().err().unwrap_or_default();
+    Response::new().add_attributes(vec![attr("action", "hook_failed"), attr("error", error)])
+}
+
+pub fn query_hooks(deps: Deps, address: Option<String>) -> StdResult<HooksResponse> {
+    let hooks = match address {
+        Some(address) => {
+            let address = deps.api.addr_validate(&address)?;
+            TRADER_HOOKS.may_load(deps.storage, address)?
+        }
+        None => GLOBAL_HOOKS.may_load(deps.storage)?,
+    };
+
+    Ok(HooksResponse {
+        hooks: hooks
+            .unwrap_or_default()
+            .into_iter()
+            .map(|hook| hook.to_string())
+            .collect(),
+    })
+}
@@ -0,0 +1,108 @@
+use cosmwasm_std::{attr, DepsMut, Env, MessageInfo, Response, StdResult, Storage, Uint128};
+
+use crate::contract::{
+    add_orderbook, assign_priority, check_enabled, check_order_funds, get_pair_info,
+};
+use crate::error::ContractError;
+use crate::events;
+use crate::group::check_group_id;
+use crate::ledger::lock_order;
+use crate::pair::{check_circuit_breaker, check_pair_status, check_price_band, PairAction};
+use crate::state::{side_key, IcebergReserve, BUYERS, CONFIG, ICEBERGS, SELLERS};
+
+use classic_terrapexc::trading::TraderRecord;
+
+//////////////////////////////////////////////////
+// Description:  Rest an order showing only part of its size
+// Params: [1] - Order with its full size
+//         [2] - Size of every visible slice
+/////////////////////////////////////////////////
+pub fn execute_place_iceberg(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    order: TraderRecord,
+    display_quantity: Uint128,
+) -> Result<Response, ContractError> {
+    check_enabled(&deps, &info)?;
+    let cfg = CONFIG.load(deps.storage)?;
+    let pair_info = get_pair_info(&cfg.pair_list, order.pair_id)?;
+    check_pair_status(deps.storage, order.pair_id, PairAction::Place)?;
+    check_circuit_breaker(deps.storage, env.block.time.seconds(), order.pair_id)?;
+
+    if order.address != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if order.current_stock_amount.is_zero() || display_quantity.is_zero() || order.price.is_zero() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+    if display_quantity >= order.current_stock_amount {
+        return Err(ContractError::InvalidInput {});
+    }
+    check_price_band(deps.storage, order.pair_id, order.price)?;
+    check_order_funds(
+        deps.storage,
+        &deps.querier,
+        deps.api,
+        &env,
+        &pair_info,
+        &order,
+    )?;
+
+    check_group_id(deps.storage, &order.id)?;
+
+    let hidden_quantity = order.current_stock_amount - display_quantity;
+    let shown = TraderRecord {
+        order_stock_amount: display_quantity,
+        current_stock_amount: display_quantity,
+        ..order.clone()
+    };
+    add_orderbook(deps.storage, shown.clone())?;
+    // the ledger lock covers the hidden part too, slices shown later are already funded
+    lock_order(deps.storage, &order, hidden_quantity)?;
+    ICEBERGS.save(
+        deps.storage,
+        (side_key(order.is_buy), order.id.clone()),
+        &IcebergReserve {
+            display_quantity,
+            hidden_quantity,
+        },
+    )?;
+
+    Ok(Response::new()
+        // only the visible slice is public, the reserve stays hidden
+        .add_event(events::order_placed(&shown, "iceberg"))
+        .add_attributes(vec![
+            attr("action", "place_iceberg"),
+            attr("address", order.address),
+            attr("order_id", order.id),
+        ]))
+}
+
+// Shows the next slice of an iceberg whose visible part was filled.
+// The slice goes to the back of the queue. Returns false if there is nothing left to show.
+pub fn refill_iceberg(storage: &mut dyn Storage, record: &TraderRecord) -> StdResult<bool> {
+    let key = (side_key(record.is_buy), record.id.clone());
+    let mut reserve = match ICEBERGS.may_load(storage, key.clone())? {
+        Some(reserve) if !reserve.hidden_quantity.is_zero() => reserve,
+        _ => return Ok(false),
+    };
+
+    let slice = reserve.display_quantity.min(reserve.hidden_quantity);
+    reserve.hidden_quantity -= slice;
+    let book = if record.is_buy { &BUYERS } else { &SELLERS };
+    let shown = TraderRecord {
+        order_stock_amount: slice,
+        current_stock_amount: slice,
+        ..record.clone()
+    };
+    book.save(storage, record.id.clone(), &shown)?;
+    assign_priority(storage, &shown)?;
+
+    if reserve.hidden_quantity.is_zero() {
+        ICEBERGS.remove(storage, key);
+    } else {
+        ICEBERGS.save(storage, key, &reserve)?;
+    }
+    Ok(true)
+}
@@ -0,0 +1,600 @@
+use cosmwasm_std::{
+    attr, Addr, Api, Deps, DepsMut, Env, MessageInfo, Order, Reply, Response, StdError, StdResult,
+    Storage, SubMsg, Uint128,
+};
+use cw20::Denom;
+use cw_storage_plus::U8Key;
+
+use crate::contract::{check_enabled, check_owner, NORMAL_DECIMAL};
+use crate::error::ContractError;
+use crate::msg::{
+    AssetBalance, AssetSolvency, BalancesResponse, SolvencyResponse, TokenTransferMode,
+};
+use crate::pair::load_pair_settings;
+use crate::state::{
+    side_key, LedgerBalance, OrderLock, PendingDeposit, BALANCES, BUYERS, CONFIG, GROUP_OF,
+    LIABILITIES, ORDER_GROUPS, ORDER_LOCKS, PENDING_DEPOSIT, PENDING_EXIT_LOCKS, SELLERS,
+};
+use crate::util;
+
+pub const DEPOSIT_REPLY_ID: u64 = 3;
+
+use classic_terrapexc::asset::AssetInfo;
+use classic_terrapexc::trading::{PairInfo, TraderRecord};
+
+//////////////////////////////////////////////////
+// Description:  Credit native funds sent along to the sender's ledger balance
+/////////////////////////////////////////////////
+pub fn execute_deposit(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    check_enabled(&deps, &info)?;
+    check_ledger(deps.storage)?;
+    if info.funds.is_empty() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+
+    let mut attributes = vec![
+        attr("action", "deposit"),
+        attr("address", info.sender.clone()),
+    ];
+    for coin in info.funds.iter() {
+        let asset = AssetInfo::NativeToken {
+            denom: coin.denom.clone(),
+        };
+        check_listed_asset(deps.storage, &asset)?;
+        credit(deps.storage, &info.sender, &asset_key(&asset), coin.amount)?;
+        add_liability(deps.storage, &asset_key(&asset), coin.amount)?;
+        attributes.push(attr("deposit", coin.to_string()));
+    }
+
+    Ok(Response::new().add_attributes(attributes))
+}
+
+//////////////////////////////////////////////////
+// Description:  Credit CW20 tokens received through Receive to the sender's ledger balance
+// Params: [1] - Original sender of the tokens
+//         [2] - Token contract
+//         [3] - Amount
+/////////////////////////////////////////////////
+pub fn execute_receive_deposit(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    sender: Addr,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    check_enabled(&deps, &info)?;
+    check_ledger(deps.storage)?;
+
+    let asset = AssetInfo::Token {
+        contract_addr: info.sender.to_string(),
+    };
+    check_listed_asset(deps.storage, &asset)?;
+    // what such tokens deliver is only known from the balance before and after, see DepositToken
+    if fee_on_transfer(deps.storage, &asset)? {
+        return Err(ContractError::UnacceptableToken {});
+    }
+    if amount.is_zero() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+
+    // the tokens already arrived, the contract must hold them on top of what it owes
+    let holdings = util::get_token_amount(
+        deps.querier,
+        Denom::Cw20(info.sender.clone()),
+        env.contract.address,
+    )?;
+    let liabilities = LIABILITIES
+        .may_load(deps.storage, asset_key(&asset))?
+        .unwrap_or_default();
+    if holdings < liabilities + amount {
+        return Err(ContractError::InsufficientFunds {});
+    }
+    credit(deps.storage, &sender, &asset_key(&asset), amount)?;
+    add_liability(deps.storage, &asset_key(&asset), amount)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "deposit"),
+        attr("address", sender),
+        attr("token", info.sender),
+        attr("amount", amount),
+    ]))
+}
+
+//////////////////////////////////////////////////
+// Description:  Pull CW20 tokens the sender approved, the reply credits what arrived
+// Params: [1] - Token contract
+//         [2] - Amount to pull
+/////////////////////////////////////////////////
+pub fn execute_deposit_token(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    check_enabled(&deps, &info)?;
+    check_ledger(deps.storage)?;
+    if amount.is_zero() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+
+    let token = deps.api.addr_validate(&token)?;
+    check_listed_asset(
+        deps.storage,
+        &AssetInfo::Token {
+            contract_addr: token.to_string(),
+        },
+    )?;
+    let holdings = util::get_token_amount(
+        deps.querier,
+        Denom::Cw20(token.clone()),
+        env.contract.address.clone(),
+    )?;
+    PENDING_DEPOSIT.save(
+        deps.storage,
+        &PendingDeposit {
+            address: info.sender.clone(),
+            token: token.clone(),
+            holdings,
+        },
+    )?;
+
+    let message = util::transfer_from_token_message(
+        info.sender.clone(),
+        Denom::Cw20(token.clone()),
+        amount,
+        env.contract.address,
+    )?;
+    Ok(Response::new()
+        .add_submessage(SubMsg::reply_on_success(message, DEPOSIT_REPLY_ID))
+        .add_attributes(vec![
+            attr("action", "deposit_token"),
+            attr("address", info.sender),
+            attr("token", token),
+            attr("amount", amount),
+        ]))
+}
+
+// Reply of the TransferFrom of a pulled deposit, credits the growth of the contract's holdings.
+pub fn deposit_received(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    msg.result.into_result().map_err(StdError::generic_err)?;
+    let pending = PENDING_DEPOSIT.load(deps.storage)?;
+    PENDING_DEPOSIT.remove(deps.storage);
+
+    let holdings = util::get_token_amount(
+        deps.querier,
+        Denom::Cw20(pending.token.clone()),
+        env.contract.address,
+    )?;
+    let received = holdings.saturating_sub(pending.holdings);
+    if received.is_zero() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+    let asset = pending.token.to_string();
+    credit(deps.storage, &pending.address, &asset, received)?;
+    add_liability(deps.storage, &asset, received)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "deposit"),
+        attr("address", pending.address),
+        attr("token", pending.token),
+        attr("amount", received),
+    ]))
+}
+
+//////////////////////////////////////////////////
+// Description:  Pay out free ledger balance to the sender
+// Params: [1] - Asset to withdraw
+//         [2] - Amount
+/////////////////////////////////////////////////
+pub fn execute_withdraw(
+    deps: DepsMut,
+    info: MessageInfo,
+    asset: AssetInfo,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    if amount.is_zero() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+    // funds can always leave, even once the ledger or the contract is switched off
+    debit(deps.storage, &info.sender, &asset_key(&asset), amount)?;
+    remove_liability(deps.storage, &asset_key(&asset), amount)?;
+
+    let denom = asset_denom(deps.api, &asset)?;
+    let message = util::transfer_token_message(denom, amount, info.sender.clone())?;
+
+    Ok(Response::new().add_message(message).add_attributes(vec![
+        attr("action", "withdraw"),
+        attr("address", info.sender),
+        attr("amount", amount),
+    ]))
+}
+
+//////////////////////////////////////////////////
+// Description:  Only owner can execute it, sweeps what the contract holds beyond its liabilities
+// Params: [1] - Asset to reconcile
+/////////////////////////////////////////////////
+pub fn execute_reconcile(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    asset: AssetInfo,
+) -> Result<Response, ContractError> {
+    check_owner(&deps, &info)?;
+    let cfg = CONFIG.load(deps.storage)?;
+    let treasury = match cfg.treasury {
+        Some(treasury) => deps.api.addr_humanize(&treasury)?,
+        None => return Err(ContractError::InvalidInput {}),
+    };
+
+    let denom = asset_denom(deps.api, &asset)?;
+    let holdings = util::get_token_amount(deps.querier, denom.clone(), env.contract.address)?;
+    let liabilities = LIABILITIES
+        .may_load(deps.storage, asset_key(&asset))?
+        .unwrap_or_default();
+    let surplus = holdings.saturating_sub(liabilities);
+    if surplus.is_zero() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+
+    Ok(Response::new()
+        .add_message(util::transfer_token_message(
+            denom,
+            surplus,
+            treasury.clone(),
+        )?)
+        .add_attributes(vec![
+            attr("action", "reconcile"),
+            attr("asset", asset_key(&asset)),
+            attr("treasury", treasury),
+            attr("amount", surplus),
+        ]))
+}
+
+// Ledger balances are keyed by the native denom or the token contract address.
+pub fn asset_key(asset: &AssetInfo) -> String {
+    match asset {
+        AssetInfo::NativeToken { denom } => denom.clone(),
+        AssetInfo::Token { contract_addr } => contract_addr.to_string(),
+    }
+}
+
+// What an order of `quantity` at `price` commits: the from asset for a buy, the to asset for a sell.
+pub fn order_commitment(
+    pair_info: &PairInfo,
+    is_buy: bool,
+    quantity: Uint128,
+    price: Uint128,
+) -> (String, Uint128) {
+    if is_buy {
+        (
+            asset_key(&pair_info.from_asset),
+            quantity * price / Uint128::from(NORMAL_DECIMAL),
+        )
+    } else {
+        (asset_key(&pair_info.to_asset), quantity)
+    }
+}
+
+pub fn asset_denom(api: &dyn Api, asset: &AssetInfo) -> StdResult<Denom> {
+    Ok(match asset {
+        AssetInfo::NativeToken { denom } => Denom::Native(denom.clone()),
+        AssetInfo::Token { contract_addr } => {
+            Denom::Cw20(api.addr_validate(contract_addr.as_str())?)
+        }
+    })
+}
+
+pub fn add_liability(storage: &mut dyn Storage, asset: &str, amount: Uint128) -> StdResult<()> {
+    let liability = LIABILITIES
+        .may_load(storage, asset.to_string())?
+        .unwrap_or_default();
+    LIABILITIES.save(storage, asset.to_string(), &(liability + amount))
+}
+
+pub fn remove_liability(storage: &mut dyn Storage, asset: &str, amount: Uint128) -> StdResult<()> {
+    let liability = LIABILITIES
+        .may_load(storage, asset.to_string())?
+        .unwrap_or_default();
+    LIABILITIES.save(
+        storage,
+        asset.to_string(),
+        &liability.saturating_sub(amount),
+    )
+}
+
+pub fn ledger_enabled(storage: &dyn Storage) -> StdResult<bool> {
+    Ok(CONFIG.load(storage)?.ledger)
+}
+
+fn check_ledger(storage: &dyn Storage) -> Result<(), ContractError> {
+    if !ledger_enabled(storage)? {
+        return Err(ContractError::Disabled {});
+    }
+    Ok(())
+}
+
+// Deposits are accepted for assets of at least one pair whose tokens are not rejected.
+fn check_listed_asset(storage: &dyn Storage, asset: &AssetInfo) -> Result<(), ContractError> {
+    let key = asset_key(asset);
+    let cfg = CONFIG.load(storage)?;
+    for (pair_id, pair_info) in cfg.pair_list.iter().enumerate() {
+        if asset_key(&pair_info.from_asset) != key && asset_key(&pair_info.to_asset) != key {
+            continue;
+        }
+        let settings = load_pair_settings(storage, Uint128::from(pair_id as u128))?;
+        if settings.token_transfer_mode != TokenTransferMode::Rejected {
+            return Ok(());
+        }
+    }
+    Err(ContractError::UnacceptableToken {})
+}
+
+// Tokens flagged as taking a fee or rebasing on transfer by any pair listing them.
+fn fee_on_transfer(storage: &dyn Storage, asset: &AssetInfo) -> StdResult<bool> {
+    let key = asset_key(asset);
+    let cfg = CONFIG.load(storage)?;
+    for (pair_id, pair_info) in cfg.pair_list.iter().enumerate() {
+        if asset_key(&pair_info.from_asset) != key && asset_key(&pair_info.to_asset) != key {
+            continue;
+        }
+        let settings = load_pair_settings(storage, Uint128::from(pair_id as u128))?;
+        if settings.token_transfer_mode == TokenTransferMode::FeeOnTransfer {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// In ledger mode a resting order's funds are locked, anything else settles from the free balance.
+// Only a lock the owner holds under the order's id counts towards it.
+pub fn check_ledger_funds(
+    storage: &dyn Storage,
+    pair_info: &PairInfo,
+    order: &TraderRecord,
+) -> Result<(), ContractError> {
+    let (asset, amount) = order_commitment(
+        pair_info,
+        order.is_buy,
+        order.current_stock_amount,
+        order.price,
+    );
+    let locked = match ORDER_LOCKS.may_load(storage, order_lock_key(order))? {
+        Some(lock) if lock.asset == asset => lock.amount,
+        _ => Uint128::zero(),
+    };
+    let balance = BALANCES
+        .may_load(storage, (order.address.clone(), asset))?
+        .unwrap_or_default();
+    if balance.free + locked < amount {
+        return Err(ContractError::InsufficientFunds {});
+    }
+    Ok(())
+}
+
+// Free ledger balance of `address` in `asset`.
+pub fn free_balance(storage: &dyn Storage, address: &Addr, asset: &str) -> StdResult<Uint128> {
+    Ok(BALANCES
+        .may_load(storage, (address.clone(), asset.to_string()))?
+        .unwrap_or_default()
+        .free)
+}
+
+fn order_lock_key(record: &TraderRecord) -> (Addr, U8Key, String) {
+    (
+        record.address.clone(),
+        side_key(record.is_buy),
+        record.id.clone(),
+    )
+}
+
+// Locks what `quantity` of the resting order commits, a no-op unless the ledger is on.
+pub fn lock_order(
+    storage: &mut dyn Storage,
+    record: &TraderRecord,
+    quantity: Uint128,
+) -> Result<(), ContractError> {
+    let cfg = CONFIG.load(storage)?;
+    if !cfg.ledger {
+        return Ok(());
+    }
+    let pair_info = match cfg.pair_list.get(record.pair_id.u128() as usize) {
+        Some(pair_info) => pair_info,
+        None => return Err(ContractError::InvalidInput {}),
+    };
+    let (asset, amount) = order_commitment(pair_info, record.is_buy, quantity, record.price);
+
+    let balance_key = (record.address.clone(), asset.clone());
+    let mut balance = BALANCES
+        .may_load(storage, balance_key.clone())?
+        .unwrap_or_default();
+    if balance.free < amount {
+        return Err(ContractError::InsufficientFunds {});
+    }
+    balance.free -= amount;
+    balance.locked += amount;
+    BALANCES.save(storage, balance_key, &balance)?;
+
+    let lock_key = order_lock_key(record);
+    let mut lock = ORDER_LOCKS
+        .may_load(storage, lock_key.clone())?
+        .unwrap_or(OrderLock {
+            asset,
+            amount: Uint128::zero(),
+        });
+    lock.amount += amount;
+    ORDER_LOCKS.save(storage, lock_key, &lock)?;
+    Ok(())
+}
+
+// Frees what `quantity` of the resting order committed, or its whole lock if None.
+// Orders placed without a lock release nothing.
+pub fn release_order(
+    storage: &mut dyn Storage,
+    record: &TraderRecord,
+    quantity: Option<Uint128>,
+) -> StdResult<()> {
+    let lock_key = order_lock_key(record);
+    let mut lock = match ORDER_LOCKS.may_load(storage, lock_key.clone())? {
+        Some(lock) => lock,
+        None => return Ok(()),
+    };
+    let amount = match quantity {
+        Some(quantity) if record.is_buy => {
+            (quantity * record.price / Uint128::from(NORMAL_DECIMAL)).min(lock.amount)
+        }
+        Some(quantity) => quantity.min(lock.amount),
+        None => lock.amount,
+    };
+
+    lock.amount -= amount;
+    if lock.amount.is_zero() {
+        ORDER_LOCKS.remove(storage, lock_key);
+    } else {
+        ORDER_LOCKS.save(storage, lock_key, &lock)?;
+    }
+
+    let balance_key = (record.address.clone(), lock.asset);
+    let mut balance = BALANCES
+        .may_load(storage, balance_key.clone())?
+        .unwrap_or_default();
+    balance.locked = balance.locked.saturating_sub(amount);
+    balance.free += amount;
+    BALANCES.save(storage, balance_key, &balance)
+}
+
+// Moves a fill between free balances: the buyer pays `move_amount * price` of the
+// from asset, the seller delivers `move_amount` of the to asset.
+pub fn settle_ledger(
+    storage: &mut dyn Storage,
+    pair_info: &PairInfo,
+    buyer: &Addr,
+    seller: &Addr,
+    move_amount: Uint128,
+    price: Uint128,
+) -> Result<(), ContractError> {
+    let (from_asset, cost) = order_commitment(pair_info, true, move_amount, price);
+    let to_asset = asset_key(&pair_info.to_asset);
+
+    debit(storage, buyer, &from_asset, cost)?;
+    credit(storage, seller, &from_asset, cost)?;
+    debit(storage, seller, &to_asset, move_amount)?;
+    credit(storage, buyer, &to_asset, move_amount)?;
+    lock_exit_legs(storage, buyer)?;
+    lock_exit_legs(storage, seller)?;
+    Ok(())
+}
+
+// Locks the exit legs the owner's bracket entries spawned, out of the proceeds of the entry fills.
+// Legs the free balance does not cover yet wait for the owner's next settled fill,
+// legs that left the book are forgotten.
+fn lock_exit_legs(storage: &mut dyn Storage, owner: &Addr) -> StdResult<()> {
+    let pending = PENDING_EXIT_LOCKS
+        .prefix(owner.clone())
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<(Vec<u8>, Uint128)>>>()?;
+
+    for (id, quantity) in pending {
+        let id = String::from_utf8(id).unwrap();
+        let record = match exit_leg(storage, &id)? {
+            Some(record) if record.address == *owner => record,
+            _ => {
+                PENDING_EXIT_LOCKS.remove(storage, (owner.clone(), id));
+                continue;
+            }
+        };
+        if lock_order(storage, &record, quantity).is_ok() {
+            PENDING_EXIT_LOCKS.remove(storage, (owner.clone(), id));
+        }
+    }
+    Ok(())
+}
+
+// The resting limit leg `id` of the group it belongs to.
+fn exit_leg(storage: &dyn Storage, id: &str) -> StdResult<Option<TraderRecord>> {
+    let group = match GROUP_OF.may_load(storage, id.to_string())? {
+        Some(group_id) => ORDER_GROUPS.load(storage, group_id)?,
+        None => return Ok(None),
+    };
+    let book = if group.is_buy { &BUYERS } else { &SELLERS };
+    book.may_load(storage, id.to_string())
+}
+
+fn credit(
+    storage: &mut dyn Storage,
+    address: &Addr,
+    asset: &str,
+    amount: Uint128,
+) -> StdResult<()> {
+    let key = (address.clone(), asset.to_string());
+    let mut balance = BALANCES.may_load(storage, key.clone())?.unwrap_or_default();
+    balance.free += amount;
+    BALANCES.save(storage, key, &balance)
+}
+
+fn debit(
+    storage: &mut dyn Storage,
+    address: &Addr,
+    asset: &str,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    let key = (address.clone(), asset.to_string());
+    let mut balance: LedgerBalance = BALANCES.may_load(storage, key.clone())?.unwrap_or_default();
+    if balance.free < amount {
+        return Err(ContractError::InsufficientFunds {});
+    }
+    balance.free -= amount;
+    BALANCES.save(storage, key, &balance)?;
+    Ok(())
+}
+
+pub fn query_balances(deps: Deps, address: String) -> StdResult<BalancesResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let balances = BALANCES
+        .prefix(address.clone())
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            item.map(|(asset, balance)| AssetBalance {
+                asset: String::from_utf8(asset).unwrap(),
+                free: balance.free,
+                locked: balance.locked,
+            })
+        })
+        .collect::<StdResult<Vec<AssetBalance>>>()?;
+
+    Ok(BalancesResponse { address, balances })
+}
+
+// Compares the ledger's liabilities with what the contract holds, for every listed asset.
+pub fn query_solvency(deps: Deps, env: Env) -> StdResult<SolvencyResponse> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let mut listed: Vec<AssetInfo> = vec![];
+    for pair_info in cfg.pair_list.iter() {
+        for asset in [&pair_info.from_asset, &pair_info.to_asset] {
+            if !listed
+                .iter()
+                .any(|seen| asset_key(seen) == asset_key(asset))
+            {
+                listed.push(asset.clone());
+            }
+        }
+    }
+
+    let mut assets: Vec<AssetSolvency> = vec![];
+    for asset in listed {
+        let denom = asset_denom(deps.api, &asset)?;
+        let holdings = util::get_token_amount(deps.querier, denom, env.contract.address.clone())
+            .map_err(|err| StdError::generic_err(err.to_string()))?;
+        let liabilities = LIABILITIES
+            .may_load(deps.storage, asset_key(&asset))?
+            .unwrap_or_default();
+        assets.push(AssetSolvency {
+            asset: asset_key(&asset),
+            liabilities,
+            holdings,
+            solvent: holdings >= liabilities,
+        });
+    }
+
+    Ok(SolvencyResponse { assets })
+}
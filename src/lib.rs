@@ -1,5 +1,18 @@
+pub mod claim;
 pub mod contract;
+pub mod events;
+pub mod factory;
+pub mod group;
+pub mod hooks;
+pub mod iceberg;
+pub mod ledger;
+pub mod matching;
+pub mod msg;
+pub mod pair;
+pub mod route;
+pub mod simulate;
 pub mod state;
+pub mod trigger;
 pub mod util;
 
 mod error;
@@ -0,0 +1,445 @@
+use cosmwasm_std::{
+    Addr, Api, Env, Event, Order, QuerierWrapper, StdResult, Storage, SubMsg, Uint128,
+};
+use cw20::Denom;
+
+use crate::claim::PAYOUT_REPLY_ID;
+use crate::contract::{
+    cancel_resting_order, check_order_funds, evict_order, finish_maker_order, reduce_resting_order,
+    NORMAL_DECIMAL,
+};
+use crate::error::ContractError;
+use crate::events;
+use crate::group::on_order_filled;
+use crate::ledger::{ledger_enabled, release_order, settle_ledger};
+use crate::msg::SelfTradePrevention;
+use crate::pair::update_pair_market;
+use crate::state::{
+    pair_key, side_key, PendingPayout, BUYERS, LAST_PRICES, ORDER_INDEX, PENDING_PAYOUTS, SELLERS,
+};
+use crate::trigger::update_trailing_stops;
+use crate::util;
+
+use classic_terrapexc::asset::AssetInfo;
+use classic_terrapexc::trading::{PairInfo, TraderRecord};
+
+pub struct BookFill {
+    pub order_id: String,
+    pub maker: Addr,
+    pub price: Uint128,
+    pub quantity: Uint128,
+}
+
+pub struct BookPlan {
+    pub fills: Vec<BookFill>,
+    // own resting orders the taker ran into, with the quantity that would have traded
+    pub self_trades: Vec<(TraderRecord, Uint128)>,
+    // the book ran out, or self trade prevention stopped the taker, before it was filled
+    pub exhausted: bool,
+}
+
+// Walks the resting orders opposite to `is_buy` on `pair_id`, best price first,
+// and plans fills of `taker` for up to `quantity`. Makers priced worse than
+// `limit_price` are skipped, makers of `taker` itself are handled by `stp`.
+pub fn plan_book_fills(
+    storage: &dyn Storage,
+    pair_id: Uint128,
+    taker: &Addr,
+    is_buy: bool,
+    quantity: Uint128,
+    limit_price: Option<Uint128>,
+    stp: SelfTradePrevention,
+) -> StdResult<BookPlan> {
+    plan_fills(
+        storage,
+        pair_id,
+        taker,
+        is_buy,
+        quantity,
+        None,
+        limit_price,
+        stp,
+    )
+}
+
+// Plans a buy of as much as `budget` of the from asset pays for, at any price.
+pub fn plan_book_budget(
+    storage: &dyn Storage,
+    pair_id: Uint128,
+    taker: &Addr,
+    budget: Uint128,
+    stp: SelfTradePrevention,
+) -> StdResult<BookPlan> {
+    plan_fills(
+        storage,
+        pair_id,
+        taker,
+        true,
+        Uint128::new(u128::MAX),
+        Some(budget),
+        None,
+        stp,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn plan_fills(
+    storage: &dyn Storage,
+    pair_id: Uint128,
+    taker: &Addr,
+    is_buy: bool,
+    quantity: Uint128,
+    budget: Option<Uint128>,
+    limit_price: Option<Uint128>,
+    stp: SelfTradePrevention,
+) -> StdResult<BookPlan> {
+    let book = if is_buy { &SELLERS } else { &BUYERS };
+    // the opposite side of the pair in price-time priority, walked only as far as it fills
+    let makers = ORDER_INDEX
+        .prefix((pair_key(pair_id), side_key(!is_buy)))
+        .range(storage, None, None, Order::Ascending);
+
+    let mut plan = BookPlan {
+        fills: vec![],
+        self_trades: vec![],
+        exhausted: false,
+    };
+    let mut remaining = quantity;
+    let mut remaining_budget = budget;
+    // the budget ran out on a maker, not the book
+    let mut budget_spent = false;
+    for item in makers {
+        if remaining.is_zero() {
+            break;
+        }
+        let (_, order_id) = item?;
+        let maker = book.load(storage, order_id)?;
+        let beyond_limit = match limit_price {
+            Some(limit) if is_buy => maker.price > limit,
+            Some(limit) => maker.price < limit,
+            None => false,
+        };
+        if beyond_limit {
+            break;
+        }
+
+        let mut fill_amount = remaining.min(maker.current_stock_amount);
+        // what is left of the budget no longer pays for all of the next maker
+        if let Some(remaining_budget) = remaining_budget {
+            let affordable = remaining_budget
+                .checked_mul(Uint128::from(NORMAL_DECIMAL))
+                .ok()
+                .and_then(|value| value.checked_div(maker.price).ok())
+                .unwrap_or(fill_amount);
+            if affordable < fill_amount {
+                fill_amount = affordable;
+                budget_spent = true;
+            }
+        }
+        if fill_amount.is_zero() {
+            break;
+        }
+        let cost = fill_amount * maker.price / Uint128::from(NORMAL_DECIMAL);
+        remaining -= fill_amount;
+        remaining_budget = remaining_budget.map(|budget| budget - cost);
+        if maker.address == *taker {
+            plan.self_trades.push((maker, fill_amount));
+            match stp {
+                SelfTradePrevention::CancelNewest | SelfTradePrevention::CancelBoth => {
+                    budget_spent = false;
+                    break;
+                }
+                // the taker goes on as if the order had not rested
+                SelfTradePrevention::CancelOldest => {
+                    remaining += fill_amount;
+                    remaining_budget = remaining_budget.map(|budget| budget + cost);
+                    budget_spent = false;
+                }
+                SelfTradePrevention::DecrementAndCancel => {}
+            }
+        } else {
+            plan.fills.push(BookFill {
+                order_id: maker.id,
+                maker: maker.address,
+                price: maker.price,
+                quantity: fill_amount,
+            });
+        }
+        if budget_spent {
+            break;
+        }
+    }
+    plan.exhausted = match remaining_budget {
+        Some(remaining_budget) => !budget_spent && !remaining_budget.is_zero(),
+        None => !remaining.is_zero(),
+    };
+
+    Ok(plan)
+}
+
+// Plans with `plan` until every planned maker can settle its fill, evicting all the ones
+// of a plan that can not before planning again. Returns the plan and the events of the evictions.
+pub fn plan_funded_fills<F>(
+    storage: &mut dyn Storage,
+    api: &dyn Api,
+    querier: QuerierWrapper,
+    env: &Env,
+    pair_info: &PairInfo,
+    is_buy: bool,
+    plan: F,
+) -> Result<(BookPlan, Vec<Event>), ContractError>
+where
+    F: Fn(&dyn Storage) -> StdResult<BookPlan>,
+{
+    let book = if is_buy { &SELLERS } else { &BUYERS };
+    let mut events: Vec<Event> = vec![];
+    // makers already known to settle are not queried again by later plans
+    let mut funded: Vec<String> = vec![];
+    loop {
+        let planned = plan(storage)?;
+        let mut evicted = false;
+        for fill in planned.fills.iter() {
+            if funded.contains(&fill.order_id) {
+                continue;
+            }
+            let maker = book.load(storage, fill.order_id.clone())?;
+            match check_order_funds(storage, &querier, api, env, pair_info, &maker) {
+                Ok(()) => funded.push(fill.order_id.clone()),
+                Err(err) => {
+                    events.push(evict_order(storage, &maker, &err)?);
+                    evicted = true;
+                }
+            }
+        }
+        if !evicted {
+            return Ok((planned, events));
+        }
+    }
+}
+
+// Resolves the planned self trades, then reduces the maker orders of planned fills,
+// removing the ones that are fully filled. Returns the events of iceberg slices shown
+// in place of filled ones.
+pub fn apply_book_fills(
+    storage: &mut dyn Storage,
+    is_buy: bool,
+    plan: &BookPlan,
+    stp: SelfTradePrevention,
+) -> StdResult<Vec<Event>> {
+    let book = if is_buy { &SELLERS } else { &BUYERS };
+    let mut events: Vec<Event> = vec![];
+
+    for (maker, amount) in plan.self_trades.iter() {
+        prevent_self_trade(storage, maker, *amount, stp)?;
+    }
+    for fill in plan.fills.iter() {
+        let mut record = book.load(storage, fill.order_id.clone())?;
+        release_order(storage, &record, Some(fill.quantity))?;
+        record.current_stock_amount -= fill.quantity;
+        let fully_filled = if record.current_stock_amount.is_zero() {
+            let left = finish_maker_order(storage, &record)?;
+            if !left {
+                let shown = book.load(storage, fill.order_id.clone())?;
+                events.push(events::order_placed(&shown, "iceberg"));
+            }
+            left
+        } else {
+            book.save(storage, fill.order_id.clone(), &record)?;
+            false
+        };
+        on_order_filled(storage, &record, fill.quantity, fully_filled)?;
+    }
+
+    Ok(events)
+}
+
+// Applies `stp` to an own resting order the taker would have filled with `amount`.
+// Returns true if the taker has to stop matching.
+pub fn prevent_self_trade(
+    storage: &mut dyn Storage,
+    maker: &TraderRecord,
+    amount: Uint128,
+    stp: SelfTradePrevention,
+) -> StdResult<bool> {
+    match stp {
+        SelfTradePrevention::CancelNewest => Ok(true),
+        SelfTradePrevention::CancelOldest => {
+            cancel_resting_order(storage, maker)?;
+            Ok(false)
+        }
+        SelfTradePrevention::CancelBoth => {
+            cancel_resting_order(storage, maker)?;
+            Ok(true)
+        }
+        SelfTradePrevention::DecrementAndCancel => {
+            reduce_resting_order(storage, maker, amount)?;
+            Ok(false)
+        }
+    }
+}
+
+// TransferFrom amounts of a batch summed per (token, from, to), in the order first seen.
+// What takers pay makers is pulled into the contract and paid out per (token, maker),
+// so a payout that fails can be left to claim instead of reverting the batch.
+pub struct NetTransfers {
+    contract: Addr,
+    transfers: Vec<(Addr, Addr, Addr, Uint128)>,
+    payouts: Vec<(Addr, Addr, Uint128)>,
+}
+
+impl NetTransfers {
+    pub fn new(contract: &Addr) -> Self {
+        NetTransfers {
+            contract: contract.clone(),
+            transfers: vec![],
+            payouts: vec![],
+        }
+    }
+
+    pub fn add(&mut self, token: Addr, from: &Addr, to: &Addr, amount: Uint128) {
+        if amount.is_zero() {
+            return;
+        }
+        match self
+            .transfers
+            .iter_mut()
+            .find(|(t, f, r, _)| *t == token && f == from && r == to)
+        {
+            Some((_, _, _, total)) => *total += amount,
+            None => self
+                .transfers
+                .push((token, from.clone(), to.clone(), amount)),
+        }
+    }
+
+    // What the batch already pulls from `from` in `token`.
+    pub fn pulled(&self, token: &Addr, from: &Addr) -> Uint128 {
+        self.transfers
+            .iter()
+            .filter(|(t, f, _, _)| t == token && f == from)
+            .map(|(_, _, _, amount)| *amount)
+            .sum()
+    }
+
+    // Pulls `amount` from the taker, unless the contract itself pays, and pays it out to the maker.
+    pub fn add_payout(&mut self, token: Addr, taker: &Addr, maker: &Addr, amount: Uint128) {
+        if amount.is_zero() {
+            return;
+        }
+        let contract = self.contract.clone();
+        if *taker != contract {
+            self.add(token.clone(), taker, &contract, amount);
+        }
+        match self
+            .payouts
+            .iter_mut()
+            .find(|(t, r, _)| *t == token && r == maker)
+        {
+            Some((_, _, total)) => *total += amount,
+            None => self.payouts.push((token, maker.clone(), amount)),
+        }
+    }
+
+    // Seller sends `move_amount` of the to asset, buyer pays for it in the from asset at `price`.
+    // Only token legs move, check_wallet_assets keeps native pairs out of wallet settlement.
+    pub fn add_fill(
+        &mut self,
+        pair_info: &PairInfo,
+        buyer: &Addr,
+        seller: &Addr,
+        move_amount: Uint128,
+        price: Uint128,
+        taker_is_buy: bool,
+    ) {
+        let other_move_amount = move_amount * price / Uint128::from(NORMAL_DECIMAL);
+        if let AssetInfo::Token { contract_addr, .. } = &pair_info.to_asset {
+            let token = Addr::unchecked(contract_addr);
+            if taker_is_buy {
+                self.add(token, seller, buyer, move_amount);
+            } else {
+                self.add_payout(token, seller, buyer, move_amount);
+            }
+        }
+        if let AssetInfo::Token { contract_addr, .. } = &pair_info.from_asset {
+            let token = Addr::unchecked(contract_addr);
+            if taker_is_buy {
+                self.add_payout(token, buyer, seller, other_move_amount);
+            } else {
+                self.add(token, buyer, seller, other_move_amount);
+            }
+        }
+    }
+
+    // Transfers first so the contract holds what it pays out, payouts reply on error.
+    pub fn into_messages(self, storage: &mut dyn Storage) -> Result<Vec<SubMsg>, ContractError> {
+        let contract = self.contract;
+        let mut messages = self
+            .transfers
+            .into_iter()
+            .map(|(token, from, to, amount)| {
+                let denom = Denom::Cw20(token);
+                if from == contract {
+                    util::transfer_token_message(denom, amount, to)
+                } else {
+                    util::transfer_from_token_message(from, denom, amount, to)
+                }
+                .map(SubMsg::new)
+            })
+            .collect::<Result<Vec<SubMsg>, ContractError>>()?;
+        if self.payouts.is_empty() {
+            return Ok(messages);
+        }
+
+        let mut payouts: Vec<PendingPayout> = vec![];
+        for (index, (token, recipient, amount)) in self.payouts.into_iter().enumerate() {
+            let message = util::transfer_token_message(
+                Denom::Cw20(token.clone()),
+                amount,
+                recipient.clone(),
+            )?;
+            messages.push(SubMsg::reply_on_error(
+                message,
+                PAYOUT_REPLY_ID + index as u64,
+            ));
+            payouts.push(PendingPayout {
+                recipient,
+                token,
+                amount,
+            });
+        }
+        PENDING_PAYOUTS.save(storage, &payouts)?;
+
+        Ok(messages)
+    }
+}
+
+// Settles a fill on the ledger when it is on, otherwise adds its transfers to `transfers`.
+#[allow(clippy::too_many_arguments)]
+pub fn settle_fill(
+    storage: &mut dyn Storage,
+    transfers: &mut NetTransfers,
+    pair_info: &PairInfo,
+    buyer: &Addr,
+    seller: &Addr,
+    move_amount: Uint128,
+    price: Uint128,
+    taker_is_buy: bool,
+) -> Result<(), ContractError> {
+    if ledger_enabled(storage)? {
+        return settle_ledger(storage, pair_info, buyer, seller, move_amount, price);
+    }
+    transfers.add_fill(pair_info, buyer, seller, move_amount, price, taker_is_buy);
+    Ok(())
+}
+
+// Every fill moves the last price, the pair's price history and trailing stops follow it.
+pub fn record_last_price(
+    storage: &mut dyn Storage,
+    now: u64,
+    pair_id: Uint128,
+    price: Uint128,
+) -> StdResult<()> {
+    update_pair_market(storage, now, pair_id, price)?;
+    LAST_PRICES.save(storage, pair_key(pair_id), &price)?;
+    update_trailing_stops(storage, pair_id, price)
+}
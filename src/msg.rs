@@ -0,0 +1,598 @@
+// The execute and query messages of this contract, they supersede the ones of
+// classic_terrapexc::trading which still provides InstantiateMsg, MigrateMsg and the
+// book records. `cargo schema` exports them.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use classic_terrapexc::asset::AssetInfo;
+use classic_terrapexc::trading::{PairInfo, TraderRecord};
+use cosmwasm_std::{Addr, Decimal, Uint128};
+use cw20::Cw20ReceiveMsg;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    UpdateConfig {
+        owner: Option<String>,
+        pair_list: Option<Vec<PairInfo>>,
+        enabled: Option<bool>,
+        ledger: Option<bool>,
+        treasury: Option<String>,
+        /// Code of the orderbook contracts `AddPair` instantiates, enables factory mode.
+        pair_code_id: Option<u64>,
+    },
+    /// Owner only: lists a pair, in factory mode on its own orderbook contract.
+    AddPair {
+        pair_info: PairInfo,
+    },
+    Receive(Cw20ReceiveMsg),
+    /// Credits the native funds sent along to the sender's ledger balance.
+    Deposit {},
+    /// Pulls `amount` of a CW20 the sender approved and credits what actually arrived,
+    /// the way to deposit tokens that take a fee or rebase on transfer.
+    DepositToken {
+        token: String,
+        amount: Uint128,
+    },
+    /// Pays out free ledger balance of the sender.
+    Withdraw {
+        asset: AssetInfo,
+        amount: Uint128,
+    },
+    /// Pays out what failed to reach the sender when its orders filled.
+    Claim {
+        asset: AssetInfo,
+    },
+    /// Owner only: sends what the contract holds of `asset` beyond its liabilities to the treasury.
+    Reconcile {
+        asset: AssetInfo,
+    },
+    /// Owner only: per pair trading settings.
+    UpdatePair {
+        pair_id: Uint128,
+        status: Option<PairStatus>,
+        self_trade_prevention: Option<SelfTradePrevention>,
+        price_band: Option<PriceBand>,
+        circuit_breaker: Option<CircuitBreaker>,
+        token_transfer_mode: Option<TokenTransferMode>,
+    },
+    Order {
+        order: TraderRecord,
+        add_order: Option<TraderRecord>,
+        /// Resting order on the other side that is partially filled.
+        update_order: Option<CounterOrder>,
+        /// Ids of resting orders on the other side that are filled entirely.
+        remove_orders: Option<Vec<String>>,
+        /// Overrides the pair default for fills against the sender's own orders.
+        self_trade_prevention: Option<SelfTradePrevention>,
+    },
+    Cancel {
+        order_id: String,
+        is_buy: bool,
+    },
+    /// Rests `order` in the trigger book until the last traded price of its pair
+    /// crosses `trigger_price`, then converts it into a market or limit order.
+    PlaceTrigger {
+        order: TraderRecord,
+        trigger_type: TriggerType,
+        trigger_price: Uint128,
+        order_type: OrderType,
+    },
+    /// Cancels the listed orders of the sender on either side, unknown ids are skipped.
+    /// Orders of a pair that refuses cancels stay and are reported as `skipped`.
+    CancelOrders {
+        order_ids: Vec<String>,
+    },
+    /// Cancels up to `limit` resting orders of the sender, of one pair if given,
+    /// repeat until the response reports `done`. Orders of a pair that refuses
+    /// cancels are reported as `skipped` and do not count towards `limit`.
+    CancelAll {
+        pair_id: Option<Uint128>,
+        limit: Option<u32>,
+    },
+    /// Owner only: removes up to `limit` resting and trigger orders of the pair,
+    /// repeat until the response reports `done` to wind a pair down.
+    ForceCancel {
+        pair_id: Uint128,
+        limit: Option<u32>,
+    },
+    /// Indexes up to `limit` more orders and triggers placed before the migration that
+    /// introduced the price index, repeat until the response reports `done`.
+    /// Only cancelling is allowed until then.
+    MigrateIndex {
+        limit: Option<u32>,
+    },
+    /// Changes the price and/or remaining quantity of a resting order, keeping its id.
+    /// Time priority is kept only when the quantity is reduced at the same price.
+    AmendOrder {
+        order_id: String,
+        new_price: Option<Uint128>,
+        new_quantity: Option<Uint128>,
+    },
+    /// Rests a stop whose trigger price trails the best price seen since
+    /// placement by `offset`, starting from the current last traded price.
+    PlaceTrailingStop {
+        order: TraderRecord,
+        offset: TrailingOffset,
+        order_type: OrderType,
+    },
+    CancelTrigger {
+        pair_id: Uint128,
+        order_id: String,
+    },
+    /// Rests a take profit limit order and a stop order on the same side.
+    /// Fills of the limit reduce the stop, firing the stop cancels the limit.
+    PlaceOco {
+        limit_order: TraderRecord,
+        stop_order: TraderRecord,
+        stop_price: Uint128,
+        stop_order_type: OrderType,
+    },
+    /// Rests only `display_quantity` of `order` in the book and keeps the rest hidden.
+    /// Each time the visible slice is filled the next one is shown at the back of the queue.
+    PlaceIceberg {
+        order: TraderRecord,
+        display_quantity: Uint128,
+    },
+    /// Rests `entry`, every fill of it spawns or grows an OCO exit pair
+    /// on the other side at `take_profit_price` and `stop_price`.
+    PlaceBracket {
+        entry: TraderRecord,
+        take_profit_price: Uint128,
+        stop_price: Uint128,
+        stop_order_type: OrderType,
+    },
+    /// Permissionless: fires every trigger order of the pair whose condition is met.
+    ExecuteTriggers {
+        pair_id: Uint128,
+        limit: Option<u32>,
+    },
+    /// Market fills `amount` of `offer_asset` through the pairs of `path` in turn,
+    /// each hop spending all the previous one received, and fails unless at least
+    /// `min_receive` of `ask_asset` comes out. Assets between hops stay in the contract.
+    Route {
+        offer_asset: AssetInfo,
+        ask_asset: AssetInfo,
+        amount: Uint128,
+        min_receive: Uint128,
+        path: Vec<Uint128>,
+    },
+    /// Registers a contract that receives a `TradeHookExecuteMsg` whenever
+    /// an order of the sender fills or is cancelled.
+    AddHook {
+        addr: String,
+    },
+    RemoveHook {
+        addr: String,
+    },
+    /// Owner only: like `AddHook`, for the orders of every trader.
+    AddGlobalHook {
+        addr: String,
+    },
+    RemoveGlobalHook {
+        addr: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    Config {},
+    Now {},
+    ListOrders {
+        is_buy: bool,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    ListTriggers {
+        pair_id: Uint128,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    LastPrice {
+        pair_id: Uint128,
+    },
+    OrderGroup {
+        group_id: String,
+    },
+    Pair {
+        pair_id: Uint128,
+    },
+    Balances {
+        address: String,
+    },
+    Solvency {},
+    /// Payouts of `address` that failed and can be claimed.
+    Claims {
+        address: String,
+    },
+    /// What a `Route` would receive against the current books. Self trade prevention
+    /// and makers that could no longer settle are not taken into account.
+    SimulateRoute {
+        offer_asset: AssetInfo,
+        ask_asset: AssetInfo,
+        amount: Uint128,
+        path: Vec<Uint128>,
+    },
+    /// Fills an order would get against the current book, without `price` at any price.
+    SimulateOrder {
+        pair_id: Uint128,
+        side: Side,
+        quantity: Uint128,
+        price: Option<Uint128>,
+    },
+    /// What a market order has to offer to receive `desired_receive`: the from asset
+    /// to buy that much of the to asset, or the to asset to sell for that much of the from asset.
+    ReverseSimulate {
+        pair_id: Uint128,
+        side: Side,
+        desired_receive: Uint128,
+    },
+    /// Hooks registered by `address`, or the global ones if omitted.
+    Hooks {
+        address: Option<String>,
+    },
+}
+
+/// Message a CW20 token sends along through `Receive`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Cw20HookMsg {
+    Deposit {},
+}
+
+/// A resting order to fill by `quantity`, its price and owner are read from the book.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CounterOrder {
+    pub order_id: String,
+    pub quantity: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchStatus {
+    Filled,
+    SelfTradePrevented,
+    /// The counter order was gone or changed since the batch was built, or the taker was cancelled.
+    Skipped,
+    /// The counter order could no longer be settled and was removed from the book.
+    Evicted,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MatchOutcome {
+    pub order_id: String,
+    pub status: MatchStatus,
+    pub quantity: Uint128,
+    pub price: Option<Uint128>,
+    pub reason: Option<String>,
+}
+
+impl MatchOutcome {
+    pub fn new(order_id: &str, status: MatchStatus) -> Self {
+        MatchOutcome {
+            order_id: order_id.to_string(),
+            status,
+            quantity: Uint128::zero(),
+            price: None,
+            reason: None,
+        }
+    }
+
+    pub fn with_reason(self, reason: &impl ToString) -> Self {
+        MatchOutcome {
+            reason: Some(reason.to_string()),
+            ..self
+        }
+    }
+}
+
+/// Data of an `Order` response, one outcome per counter order in the order they were given.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OrderResponse {
+    pub outcomes: Vec<MatchOutcome>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PairStatus {
+    #[default]
+    Active,
+    /// Only cancels are accepted.
+    CancelOnly,
+    /// Orders can rest but nothing is matched.
+    PostOnly,
+    /// Nothing is accepted, not even cancels.
+    Halted,
+}
+
+/// A stop-loss fires when the price moves against the order side
+/// (falls for a sell, rises for a buy), a take-profit when it moves in favour.
+/// A trailing stop fires like a stop-loss once the price retraces by its offset.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TriggerType {
+    StopLoss,
+    TakeProfit,
+    TrailingStop,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TrailingOffset {
+    Absolute(Uint128),
+    Percent(Decimal),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TrailingStop {
+    pub offset: TrailingOffset,
+    /// High-water mark for a sell, low-water mark for a buy.
+    pub mark: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+impl Side {
+    pub fn is_buy(&self) -> bool {
+        *self == Side::Buy
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderType {
+    /// Fills against the resting book at any price, the unfilled rest is dropped.
+    Market,
+    /// Rests in the order book at `order.price`.
+    Limit,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TriggerInfo {
+    pub id: String,
+    pub address: Addr,
+    pub is_buy: bool,
+    pub trigger_type: TriggerType,
+    pub trigger_price: Uint128,
+    pub order_type: OrderType,
+    pub trailing: Option<TrailingStop>,
+    pub price: Uint128,
+    pub current_stock_amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TriggerListResponse {
+    pub triggers: Vec<TriggerInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LastPriceResponse {
+    pub pair_id: Uint128,
+    pub price: Option<Uint128>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OrderGroupResponse {
+    pub group_id: String,
+    pub address: Addr,
+    pub pair_id: Uint128,
+    pub is_buy: bool,
+    pub limit_id: String,
+    pub stop_id: String,
+    pub entry_id: Option<String>,
+    pub take_profit_price: Uint128,
+    pub stop_price: Uint128,
+}
+
+/// What happens when a taker would fill a resting order of the same address.
+/// The taker is always the newest order, the resting one the oldest.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SelfTradePrevention {
+    /// The taker stops matching and its unfilled rest does not rest.
+    #[default]
+    CancelNewest,
+    /// The resting order is cancelled and the taker keeps matching.
+    CancelOldest,
+    CancelBoth,
+    /// Both sides are reduced by the overlapping quantity without trading.
+    DecrementAndCancel,
+}
+
+/// How a pair treats tokens whose transfers move a different amount than requested,
+/// such as tokens taking a fee or rebasing on transfer.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenTransferMode {
+    #[default]
+    Standard,
+    /// Fills settle only on the ledger. Deposits go through `DepositToken`,
+    /// which credits what actually arrived, tokens sent through `Receive` are refused.
+    FeeOnTransfer,
+    /// Nothing is placed or matched, and the pair's tokens are not accepted as deposits for it.
+    Rejected,
+}
+
+/// Orders priced further than `max_deviation` from the reference price are rejected.
+/// A zero `max_deviation` disables the band.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+pub struct PriceBand {
+    pub max_deviation: Decimal,
+    pub reference: ReferencePrice,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReferencePrice {
+    LastTrade,
+    /// Time weighted average of the last traded price over the last completed `period` seconds.
+    Twap {
+        period: u64,
+    },
+}
+
+/// Halts placing and matching for `cooldown` seconds once fills move the price
+/// by more than `threshold` within `window` seconds. A zero `threshold` disables it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+pub struct CircuitBreaker {
+    pub threshold: Decimal,
+    pub window: u64,
+    pub cooldown: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PairResponse {
+    pub pair_id: Uint128,
+    pub pair_info: PairInfo,
+    pub status: PairStatus,
+    pub self_trade_prevention: SelfTradePrevention,
+    pub price_band: Option<PriceBand>,
+    pub circuit_breaker: Option<CircuitBreaker>,
+    pub token_transfer_mode: TokenTransferMode,
+    pub reference_price: Option<Uint128>,
+    pub halted_until: Option<u64>,
+    /// Orderbook contract of the pair in factory mode, orders go there instead.
+    pub contract: Option<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AssetBalance {
+    /// Native denom or token contract address.
+    pub asset: String,
+    pub free: Uint128,
+    pub locked: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BalancesResponse {
+    pub address: Addr,
+    pub balances: Vec<AssetBalance>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AssetSolvency {
+    /// Native denom or token contract address.
+    pub asset: String,
+    pub liabilities: Uint128,
+    pub holdings: Uint128,
+    pub solvent: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SolvencyResponse {
+    pub assets: Vec<AssetSolvency>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AssetClaim {
+    /// Token contract address.
+    pub asset: String,
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ClaimsResponse {
+    pub address: Addr,
+    pub claims: Vec<AssetClaim>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RouteHop {
+    pub pair_id: Uint128,
+    pub offer_amount: Uint128,
+    pub receive_amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulateRouteResponse {
+    pub receive_amount: Uint128,
+    pub hops: Vec<RouteHop>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulatedFill {
+    pub order_id: String,
+    pub maker: Addr,
+    pub price: Uint128,
+    pub quantity: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulateOrderResponse {
+    pub fills: Vec<SimulatedFill>,
+    pub filled_quantity: Uint128,
+    /// Amount of the from asset the fills pay or receive.
+    pub cost: Uint128,
+    pub average_price: Option<Uint128>,
+    /// No fees are charged yet, always zero.
+    pub fee: Uint128,
+    pub remaining_quantity: Uint128,
+    /// Whether the remaining quantity would rest in the book.
+    pub rests: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReverseSimulateResponse {
+    pub fills: Vec<SimulatedFill>,
+    pub offer_amount: Uint128,
+    /// At least `desired_receive`, a sell can receive slightly more due to rounding.
+    pub receive_amount: Uint128,
+    pub average_price: Option<Uint128>,
+    /// No fees are charged yet, always zero.
+    pub fee: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct HooksResponse {
+    pub hooks: Vec<String>,
+}
+
+/// Notification sent to hook contracts, one per order and fill.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TradeHookMsg {
+    OrderFilled {
+        pair_id: Uint128,
+        order_id: String,
+        address: Addr,
+        is_buy: bool,
+        /// True if the order was resting in the book.
+        is_maker: bool,
+        price: Uint128,
+        quantity: Uint128,
+    },
+    OrderCancelled {
+        pair_id: Uint128,
+        order_id: String,
+        address: Addr,
+        is_buy: bool,
+        /// Quantity left when the order was cancelled.
+        quantity: Uint128,
+        /// One of user, forced, self_trade or evicted.
+        reason: String,
+    },
+}
+
+impl TradeHookMsg {
+    pub fn address(&self) -> &Addr {
+        match self {
+            TradeHookMsg::OrderFilled { address, .. } => address,
+            TradeHookMsg::OrderCancelled { address, .. } => address,
+        }
+    }
+}
+
+/// Execute message hook contracts have to accept, a hook is called at most once per
+/// transaction with every notification of it in the order they happened.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TradeHookExecuteMsg {
+    TradeHook(Vec<TradeHookMsg>),
+}
@@ -0,0 +1,268 @@
+use cosmwasm_std::{
+    attr, Deps, DepsMut, MessageInfo, Response, StdError, StdResult, Storage, Uint128,
+};
+
+use crate::contract::{check_owner, get_pair_info};
+use crate::error::ContractError;
+use crate::events;
+use crate::msg::{
+    CircuitBreaker, PairResponse, PairStatus, PriceBand, ReferencePrice, SelfTradePrevention,
+    TokenTransferMode,
+};
+use crate::state::{
+    pair_key, PairMarket, PairSettings, CONFIG, INDEX_MIGRATION, LAST_PRICES, PAIR_CONTRACTS,
+    PAIR_MARKETS, PAIR_SETTINGS,
+};
+
+pub enum PairAction {
+    Place,
+    Match,
+    Cancel,
+}
+
+//////////////////////////////////////////////////
+// Description:  Only owner can execute it
+// Params: [1] - Pair id
+//         [2] - Trading status
+//         [3] - Default self trade prevention
+//         [4] - Price band
+//         [5] - Circuit breaker
+//         [6] - Handling of non-standard tokens
+/////////////////////////////////////////////////
+#[allow(clippy::too_many_arguments)]
+pub fn execute_update_pair(
+    deps: DepsMut,
+    info: MessageInfo,
+    pair_id: Uint128,
+    status: Option<PairStatus>,
+    self_trade_prevention: Option<SelfTradePrevention>,
+    price_band: Option<PriceBand>,
+    circuit_breaker: Option<CircuitBreaker>,
+    token_transfer_mode: Option<TokenTransferMode>,
+) -> Result<Response, ContractError> {
+    check_owner(&deps, &info)?;
+    let cfg = CONFIG.load(deps.storage)?;
+    get_pair_info(&cfg.pair_list, pair_id)?;
+
+    let mut settings = load_pair_settings(deps.storage, pair_id)?;
+
+    if let Some(status) = status {
+        settings.status = status;
+    }
+
+    if let Some(self_trade_prevention) = self_trade_prevention {
+        settings.self_trade_prevention = self_trade_prevention;
+    }
+
+    if let Some(price_band) = price_band {
+        settings.price_band = Some(price_band);
+    }
+
+    if let Some(circuit_breaker) = circuit_breaker {
+        settings.circuit_breaker = Some(circuit_breaker);
+    }
+
+    if let Some(token_transfer_mode) = token_transfer_mode {
+        settings.token_transfer_mode = token_transfer_mode;
+    }
+
+    PAIR_SETTINGS.save(deps.storage, pair_key(pair_id), &settings)?;
+
+    Ok(Response::new()
+        .add_event(events::pair_updated(pair_id, &settings))
+        .add_attributes(vec![
+            attr("action", "update_pair"),
+            attr("pair_id", pair_id),
+        ]))
+}
+
+pub fn load_pair_settings(storage: &dyn Storage, pair_id: Uint128) -> StdResult<PairSettings> {
+    Ok(PAIR_SETTINGS
+        .may_load(storage, pair_key(pair_id))?
+        .unwrap_or_default())
+}
+
+// Post only pairs accept resting orders but no fills, cancel only pairs just cancels,
+// halted pairs freeze the book entirely until the owner changes the status.
+// Pairs of non-standard tokens place and match only when fills stay on the ledger.
+// Pairs with their own orderbook contract trade there only.
+// Nothing trades while a migration is still indexing the book.
+pub fn check_pair_status(
+    storage: &dyn Storage,
+    pair_id: Uint128,
+    action: PairAction,
+) -> Result<(), ContractError> {
+    let settings = load_pair_settings(storage, pair_id)?;
+    let trades = !matches!(action, PairAction::Cancel);
+    if trades && INDEX_MIGRATION.may_load(storage)?.is_some() {
+        return Err(ContractError::PairUnavailable {});
+    }
+    if trades && PAIR_CONTRACTS.has(storage, pair_key(pair_id)) {
+        return Err(ContractError::PairUnavailable {});
+    }
+    let tokens_ok = match settings.token_transfer_mode {
+        TokenTransferMode::Standard => true,
+        TokenTransferMode::FeeOnTransfer => CONFIG.load(storage)?.ledger,
+        TokenTransferMode::Rejected => false,
+    };
+    if trades && !tokens_ok {
+        return Err(ContractError::PairUnavailable {});
+    }
+
+    let allowed = match (settings.status, action) {
+        (PairStatus::Active, _) => true,
+        (PairStatus::PostOnly, PairAction::Match) => false,
+        (PairStatus::PostOnly, _) => true,
+        (PairStatus::CancelOnly, PairAction::Cancel) => true,
+        (PairStatus::CancelOnly, _) | (PairStatus::Halted, _) => false,
+    };
+    if !allowed {
+        return Err(ContractError::PairUnavailable {});
+    }
+    Ok(())
+}
+
+// Rejects placing and matching while the circuit breaker cools down.
+pub fn check_circuit_breaker(
+    storage: &dyn Storage,
+    now: u64,
+    pair_id: Uint128,
+) -> Result<(), ContractError> {
+    let market = PAIR_MARKETS
+        .may_load(storage, pair_key(pair_id))?
+        .unwrap_or_default();
+    if market.halted_until > now {
+        return Err(ContractError::CircuitBreakerHalted {
+            until: market.halted_until,
+        });
+    }
+    Ok(())
+}
+
+// Limit prices must lie within the band around the reference price.
+// Pairs that never traded have no reference and accept any price.
+pub fn check_price_band(
+    storage: &dyn Storage,
+    pair_id: Uint128,
+    price: Uint128,
+) -> Result<(), ContractError> {
+    let band = match load_pair_settings(storage, pair_id)?.price_band {
+        Some(band) if !band.max_deviation.is_zero() => band,
+        _ => return Ok(()),
+    };
+    let reference = match reference_price(storage, pair_id, &band)? {
+        Some(reference) => reference,
+        None => return Ok(()),
+    };
+
+    let deviation = reference * band.max_deviation;
+    if price < reference.saturating_sub(deviation) || price > reference + deviation {
+        return Err(ContractError::PriceOutOfBand {});
+    }
+    Ok(())
+}
+
+fn reference_price(
+    storage: &dyn Storage,
+    pair_id: Uint128,
+    band: &PriceBand,
+) -> StdResult<Option<Uint128>> {
+    let last_price = LAST_PRICES.may_load(storage, pair_key(pair_id))?;
+    match band.reference {
+        ReferencePrice::LastTrade => Ok(last_price),
+        ReferencePrice::Twap { .. } => {
+            let market = PAIR_MARKETS
+                .may_load(storage, pair_key(pair_id))?
+                .unwrap_or_default();
+            Ok(market.twap.or(last_price))
+        }
+    }
+}
+
+// Feeds a fill into the TWAP accumulator and trips the circuit breaker on large moves.
+// Must run before the fill price becomes the last price.
+pub fn update_pair_market(
+    storage: &mut dyn Storage,
+    now: u64,
+    pair_id: Uint128,
+    price: Uint128,
+) -> StdResult<()> {
+    let settings = load_pair_settings(storage, pair_id)?;
+    let mut market: PairMarket = PAIR_MARKETS
+        .may_load(storage, pair_key(pair_id))?
+        .unwrap_or_default();
+
+    match LAST_PRICES.may_load(storage, pair_key(pair_id))? {
+        Some(previous) => {
+            market.cumulative_price += previous * Uint128::from(now - market.last_fill_time);
+        }
+        None => market.twap_snapshot_time = now,
+    }
+    market.last_fill_time = now;
+
+    if let Some(PriceBand {
+        reference: ReferencePrice::Twap { period },
+        ..
+    }) = settings.price_band
+    {
+        let elapsed = now - market.twap_snapshot_time;
+        if elapsed > 0 && elapsed >= period {
+            market.twap = Some(
+                (market.cumulative_price - market.twap_snapshot_cumulative)
+                    / Uint128::from(elapsed),
+            );
+            market.twap_snapshot_time = now;
+            market.twap_snapshot_cumulative = market.cumulative_price;
+        }
+    }
+
+    if let Some(breaker) = settings.circuit_breaker {
+        let open = market.window_open_price;
+        if open.is_zero() || now >= market.window_start + breaker.window {
+            market.window_start = now;
+            market.window_open_price = price;
+        } else {
+            let moved = if price > open {
+                price - open
+            } else {
+                open - price
+            };
+            if !breaker.threshold.is_zero() && moved > open * breaker.threshold {
+                market.halted_until = now + breaker.cooldown;
+                // a new window opens with the first fill after the cooldown
+                market.window_open_price = Uint128::zero();
+            }
+        }
+    }
+
+    PAIR_MARKETS.save(storage, pair_key(pair_id), &market)
+}
+
+pub fn query_pair(deps: Deps, pair_id: Uint128) -> StdResult<PairResponse> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let pair_info = match cfg.pair_list.get(pair_id.u128() as usize) {
+        Some(pair_info) => pair_info.clone(),
+        None => return Err(StdError::not_found("pair")),
+    };
+    let settings = load_pair_settings(deps.storage, pair_id)?;
+    let market = PAIR_MARKETS.may_load(deps.storage, pair_key(pair_id))?;
+    let reference_price = match settings.price_band.as_ref() {
+        Some(band) => reference_price(deps.storage, pair_id, band)?,
+        None => LAST_PRICES.may_load(deps.storage, pair_key(pair_id))?,
+    };
+
+    Ok(PairResponse {
+        pair_id,
+        pair_info,
+        status: settings.status,
+        self_trade_prevention: settings.self_trade_prevention,
+        price_band: settings.price_band,
+        circuit_breaker: settings.circuit_breaker,
+        token_transfer_mode: settings.token_transfer_mode,
+        reference_price,
+        halted_until: market
+            .map(|market| market.halted_until)
+            .filter(|until| *until > 0),
+        contract: PAIR_CONTRACTS.may_load(deps.storage, pair_key(pair_id))?,
+    })
+}
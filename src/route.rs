@@ -0,0 +1,316 @@
+use cosmwasm_std::{
+    attr, Addr, Attribute, Deps, DepsMut, Env, Event, MessageInfo, Response, StdError, StdResult,
+    Storage, Uint128,
+};
+
+use crate::contract::{check_enabled, check_wallet_assets, get_pair_info, NORMAL_DECIMAL};
+use crate::error::ContractError;
+use crate::events::{self, Fill};
+use crate::ledger::{asset_key, settle_ledger};
+use crate::matching::{
+    apply_book_fills, plan_book_budget, plan_book_fills, plan_funded_fills, record_last_price,
+    BookFill, BookPlan, NetTransfers,
+};
+use crate::msg::{RouteHop, SelfTradePrevention, SimulateRouteResponse};
+use crate::pair::{check_circuit_breaker, check_pair_status, load_pair_settings, PairAction};
+use crate::state::CONFIG;
+use crate::trigger::{process_triggers, DEFAULT_TRIGGER_LIMIT};
+
+use classic_terrapexc::asset::AssetInfo;
+use classic_terrapexc::trading::PairInfo;
+
+// fills of a route carry no order id of the taker
+const ROUTE_ORDER_ID: &str = "route";
+
+struct Hop {
+    pair_id: Uint128,
+    pair_info: PairInfo,
+    // buys the to asset with the from asset, or sells it
+    is_buy: bool,
+}
+
+//////////////////////////////////////////////////
+// Description:  Market fill through a path of pairs, the assets between hops stay in the contract
+// Params: [1] - Offered asset
+//         [2] - Asked asset
+//         [3] - Offered amount
+//         [4] - Least amount of the asked asset to receive
+//         [5] - Pair ids to trade through
+/////////////////////////////////////////////////
+#[allow(clippy::too_many_arguments)]
+pub fn execute_route(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    offer_asset: AssetInfo,
+    ask_asset: AssetInfo,
+    amount: Uint128,
+    min_receive: Uint128,
+    path: Vec<Uint128>,
+) -> Result<Response, ContractError> {
+    check_enabled(&deps, &info)?;
+    if amount.is_zero() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+    let cfg = CONFIG.load(deps.storage)?;
+    let hops = resolve_path(&cfg.pair_list, &offer_asset, &ask_asset, &path)?;
+    if !cfg.ledger {
+        for hop in hops.iter() {
+            check_wallet_assets(&hop.pair_info)?;
+        }
+    }
+
+    let now = env.block.time.seconds();
+    let contract = env.contract.address.clone();
+    let mut transfers = NetTransfers::new(&contract);
+    let mut attributes: Vec<Attribute> = vec![];
+    let mut events: Vec<Event> = vec![];
+    let mut offer_amount = amount;
+    for (index, hop) in hops.iter().enumerate() {
+        check_hop(deps.storage, now, hop)?;
+        let stp = load_pair_settings(deps.storage, hop.pair_id)?.self_trade_prevention;
+        let (plan, evictions) = plan_funded_fills(
+            deps.storage,
+            deps.api,
+            deps.querier,
+            &env,
+            &hop.pair_info,
+            hop.is_buy,
+            |storage| plan_hop(storage, hop, &info.sender, offer_amount, stp),
+        )?;
+        events.extend(evictions);
+        if plan.exhausted {
+            return Err(ContractError::NotEnoughLiquidity {});
+        }
+        let refills = apply_book_fills(deps.storage, hop.is_buy, &plan, stp)?;
+
+        // the first hop is paid by the sender, later ones by what the contract received
+        let payer = if index == 0 { &info.sender } else { &contract };
+        let receiver = if index + 1 == hops.len() {
+            &info.sender
+        } else {
+            &contract
+        };
+        for fill in plan.fills.iter() {
+            if cfg.ledger {
+                let (buyer, seller) = if hop.is_buy {
+                    (&info.sender, &fill.maker)
+                } else {
+                    (&fill.maker, &info.sender)
+                };
+                settle_ledger(
+                    deps.storage,
+                    &hop.pair_info,
+                    buyer,
+                    seller,
+                    fill.quantity,
+                    fill.price,
+                )?;
+            } else {
+                add_route_fill(&mut transfers, hop, payer, receiver, fill);
+            }
+            events.push(events::order_filled(&Fill {
+                pair_id: hop.pair_id,
+                maker_order_id: &fill.order_id,
+                maker: &fill.maker,
+                taker_order_id: ROUTE_ORDER_ID,
+                taker: &info.sender,
+                taker_is_buy: hop.is_buy,
+                price: fill.price,
+                quantity: fill.quantity,
+            }));
+            record_last_price(deps.storage, now, hop.pair_id, fill.price)?;
+        }
+        events.extend(refills);
+
+        let (spent, received) = hop_amounts(&plan, hop.is_buy);
+        // what a buy could not spend of an earlier hop's proceeds goes back to the sender
+        if index > 0 && !cfg.ledger && spent < offer_amount {
+            let token = Addr::unchecked(asset_key(&offer_asset_of(hop)));
+            transfers.add(token, &contract, &info.sender, offer_amount - spent);
+        }
+        attributes.push(attr("pair_id", hop.pair_id));
+        attributes.push(attr("offer_amount", spent));
+        attributes.push(attr("receive_amount", received));
+        offer_amount = received;
+    }
+    if offer_amount < min_receive {
+        return Err(ContractError::MinReceiveNotReached {
+            receive: offer_amount,
+        });
+    }
+
+    for hop in hops.iter() {
+        let (trigger_attributes, trigger_events) = process_triggers(
+            deps.storage,
+            deps.api,
+            deps.querier,
+            &env,
+            &mut transfers,
+            &hop.pair_info,
+            hop.pair_id,
+            DEFAULT_TRIGGER_LIMIT,
+        )?;
+        attributes.extend(trigger_attributes);
+        events.extend(trigger_events);
+    }
+    let messages = transfers.into_messages(deps.storage)?;
+
+    Ok(Response::new()
+        .add_submessages(messages)
+        .add_attributes(vec![
+            attr("action", "route"),
+            attr("address", info.sender),
+            attr("receive", offer_amount),
+        ])
+        .add_attributes(attributes)
+        .add_events(events))
+}
+
+pub fn query_simulate_route(
+    deps: Deps,
+    env: Env,
+    offer_asset: AssetInfo,
+    ask_asset: AssetInfo,
+    amount: Uint128,
+    path: Vec<Uint128>,
+) -> StdResult<SimulateRouteResponse> {
+    let contract_err = |err: ContractError| StdError::generic_err(err.to_string());
+    let cfg = CONFIG.load(deps.storage)?;
+    let hops =
+        resolve_path(&cfg.pair_list, &offer_asset, &ask_asset, &path).map_err(contract_err)?;
+
+    let now = env.block.time.seconds();
+    let mut offer_amount = amount;
+    let mut route: Vec<RouteHop> = vec![];
+    for hop in hops.iter() {
+        check_hop(deps.storage, now, hop).map_err(contract_err)?;
+        // the contract never trades itself, so no fill is a self trade
+        let plan = plan_hop(
+            deps.storage,
+            hop,
+            &env.contract.address,
+            offer_amount,
+            SelfTradePrevention::default(),
+        )?;
+        if plan.exhausted {
+            return Err(contract_err(ContractError::NotEnoughLiquidity {}));
+        }
+        let (spent, received) = hop_amounts(&plan, hop.is_buy);
+        route.push(RouteHop {
+            pair_id: hop.pair_id,
+            offer_amount: spent,
+            receive_amount: received,
+        });
+        offer_amount = received;
+    }
+
+    Ok(SimulateRouteResponse {
+        receive_amount: offer_amount,
+        hops: route,
+    })
+}
+
+// Every pair of `path` has to hold what the previous one gives, ending in `ask_asset`.
+fn resolve_path(
+    pair_list: &[PairInfo],
+    offer_asset: &AssetInfo,
+    ask_asset: &AssetInfo,
+    path: &[Uint128],
+) -> Result<Vec<Hop>, ContractError> {
+    if path.is_empty() {
+        return Err(ContractError::InvalidInput {});
+    }
+
+    let mut asset = asset_key(offer_asset);
+    let mut hops: Vec<Hop> = vec![];
+    for pair_id in path.iter() {
+        // a pair's fills are planned against its book before any hop runs
+        if hops.iter().any(|hop| hop.pair_id == *pair_id) {
+            return Err(ContractError::InvalidInput {});
+        }
+        let pair_info = get_pair_info(pair_list, *pair_id)?;
+        let is_buy = if asset == asset_key(&pair_info.from_asset) {
+            asset = asset_key(&pair_info.to_asset);
+            true
+        } else if asset == asset_key(&pair_info.to_asset) {
+            asset = asset_key(&pair_info.from_asset);
+            false
+        } else {
+            return Err(ContractError::AssetMismatch {});
+        };
+        hops.push(Hop {
+            pair_id: *pair_id,
+            pair_info,
+            is_buy,
+        });
+    }
+    if asset != asset_key(ask_asset) {
+        return Err(ContractError::AssetMismatch {});
+    }
+
+    Ok(hops)
+}
+
+fn check_hop(storage: &dyn Storage, now: u64, hop: &Hop) -> Result<(), ContractError> {
+    check_pair_status(storage, hop.pair_id, PairAction::Match)?;
+    check_circuit_breaker(storage, now, hop.pair_id)
+}
+
+// A buy spends `offer_amount` of the from asset, a sell sells `offer_amount` of the to asset.
+fn plan_hop(
+    storage: &dyn Storage,
+    hop: &Hop,
+    taker: &Addr,
+    offer_amount: Uint128,
+    stp: SelfTradePrevention,
+) -> StdResult<BookPlan> {
+    if hop.is_buy {
+        plan_book_budget(storage, hop.pair_id, taker, offer_amount, stp)
+    } else {
+        plan_book_fills(storage, hop.pair_id, taker, false, offer_amount, None, stp)
+    }
+}
+
+// What the planned fills of a hop spend and receive, costs rounded per fill like settlement.
+fn hop_amounts(plan: &BookPlan, is_buy: bool) -> (Uint128, Uint128) {
+    let mut quantity = Uint128::zero();
+    let mut cost = Uint128::zero();
+    for fill in plan.fills.iter() {
+        quantity += fill.quantity;
+        cost += fill.quantity * fill.price / Uint128::from(NORMAL_DECIMAL);
+    }
+    if is_buy {
+        (cost, quantity)
+    } else {
+        (quantity, cost)
+    }
+}
+
+fn offer_asset_of(hop: &Hop) -> AssetInfo {
+    if hop.is_buy {
+        hop.pair_info.from_asset.clone()
+    } else {
+        hop.pair_info.to_asset.clone()
+    }
+}
+
+// The payer pays the maker through the contract, the maker delivers to the receiver.
+fn add_route_fill(
+    transfers: &mut NetTransfers,
+    hop: &Hop,
+    payer: &Addr,
+    receiver: &Addr,
+    fill: &BookFill,
+) {
+    let to_token = Addr::unchecked(asset_key(&hop.pair_info.to_asset));
+    let from_token = Addr::unchecked(asset_key(&hop.pair_info.from_asset));
+    let cost = fill.quantity * fill.price / Uint128::from(NORMAL_DECIMAL);
+    if hop.is_buy {
+        transfers.add_payout(from_token, payer, &fill.maker, cost);
+        transfers.add(to_token, &fill.maker, receiver, fill.quantity);
+    } else {
+        transfers.add_payout(to_token, payer, &fill.maker, fill.quantity);
+        transfers.add(from_token, &fill.maker, receiver, cost);
+    }
+}
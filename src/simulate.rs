@@ -0,0 +1,155 @@
+use cosmwasm_std::{Deps, Env, Order, StdError, StdResult, Uint128};
+
+use crate::contract::{get_pair_info, NORMAL_DECIMAL};
+use crate::error::ContractError;
+use crate::matching::plan_book_fills;
+use crate::msg::{
+    ReverseSimulateResponse, SelfTradePrevention, Side, SimulateOrderResponse, SimulatedFill,
+};
+use crate::pair::{check_circuit_breaker, check_pair_status, check_price_band, PairAction};
+use crate::state::{pair_key, side_key, BUYERS, CONFIG, ORDER_INDEX, SELLERS};
+
+// Plans the order like a taker against the current book. The contract stands in for
+// the trader, so no fill is a self trade and makers are assumed to settle.
+pub fn query_simulate_order(
+    deps: Deps,
+    env: Env,
+    pair_id: Uint128,
+    side: Side,
+    quantity: Uint128,
+    price: Option<Uint128>,
+) -> StdResult<SimulateOrderResponse> {
+    let contract_err = |err: ContractError| StdError::generic_err(err.to_string());
+    let cfg = CONFIG.load(deps.storage)?;
+    get_pair_info(&cfg.pair_list, pair_id).map_err(contract_err)?;
+    check_circuit_breaker(deps.storage, env.block.time.seconds(), pair_id).map_err(contract_err)?;
+    if let Some(price) = price {
+        check_price_band(deps.storage, pair_id, price).map_err(contract_err)?;
+    }
+
+    // a post only pair rests the whole order
+    let matches = check_pair_status(deps.storage, pair_id, PairAction::Match).is_ok();
+    let fills = if matches {
+        plan_book_fills(
+            deps.storage,
+            pair_id,
+            &env.contract.address,
+            side.is_buy(),
+            quantity,
+            price,
+            SelfTradePrevention::default(),
+        )?
+        .fills
+    } else {
+        vec![]
+    };
+
+    let mut filled_quantity = Uint128::zero();
+    let mut cost = Uint128::zero();
+    for fill in fills.iter() {
+        filled_quantity += fill.quantity;
+        cost += fill.quantity * fill.price / Uint128::from(NORMAL_DECIMAL);
+    }
+    let average_price = if filled_quantity.is_zero() {
+        None
+    } else {
+        Some(cost * Uint128::from(NORMAL_DECIMAL) / filled_quantity)
+    };
+    let remaining_quantity = quantity - filled_quantity;
+    let rests = price.is_some()
+        && !remaining_quantity.is_zero()
+        && check_pair_status(deps.storage, pair_id, PairAction::Place).is_ok();
+
+    Ok(SimulateOrderResponse {
+        fills: fills
+            .into_iter()
+            .map(|fill| SimulatedFill {
+                order_id: fill.order_id,
+                maker: fill.maker,
+                price: fill.price,
+                quantity: fill.quantity,
+            })
+            .collect(),
+        filled_quantity,
+        cost,
+        average_price,
+        fee: Uint128::zero(),
+        remaining_quantity,
+        rests,
+    })
+}
+
+// Walks the book like a market order, only until the fills add up to `desired_receive`.
+pub fn query_reverse_simulate(
+    deps: Deps,
+    env: Env,
+    pair_id: Uint128,
+    side: Side,
+    desired_receive: Uint128,
+) -> StdResult<ReverseSimulateResponse> {
+    let contract_err = |err: ContractError| StdError::generic_err(err.to_string());
+    let cfg = CONFIG.load(deps.storage)?;
+    get_pair_info(&cfg.pair_list, pair_id).map_err(contract_err)?;
+    check_circuit_breaker(deps.storage, env.block.time.seconds(), pair_id).map_err(contract_err)?;
+    check_pair_status(deps.storage, pair_id, PairAction::Match).map_err(contract_err)?;
+
+    let book = if side.is_buy() { &SELLERS } else { &BUYERS };
+    let makers = ORDER_INDEX
+        .prefix((pair_key(pair_id), side_key(!side.is_buy())))
+        .range(deps.storage, None, None, Order::Ascending);
+
+    let decimal = Uint128::from(NORMAL_DECIMAL);
+    let mut fills: Vec<SimulatedFill> = vec![];
+    let mut quantity = Uint128::zero();
+    let mut cost = Uint128::zero();
+    for item in makers {
+        let received = if side.is_buy() { quantity } else { cost };
+        if received >= desired_receive {
+            break;
+        }
+        let (_, order_id) = item?;
+        let maker = book.load(deps.storage, order_id)?;
+        let missing = desired_receive - received;
+        let fill_quantity = if side.is_buy() {
+            missing.min(maker.current_stock_amount)
+        } else {
+            // the smallest quantity whose proceeds cover what is missing
+            let needed = missing
+                .checked_mul(decimal)?
+                .checked_add(maker.price - Uint128::new(1))?
+                .checked_div(maker.price)
+                .unwrap_or(maker.current_stock_amount);
+            needed.min(maker.current_stock_amount)
+        };
+        quantity = quantity.checked_add(fill_quantity)?;
+        cost = cost.checked_add(fill_quantity.checked_mul(maker.price)? / decimal)?;
+        fills.push(SimulatedFill {
+            order_id: maker.id,
+            maker: maker.address,
+            price: maker.price,
+            quantity: fill_quantity,
+        });
+    }
+
+    let (offer_amount, receive_amount) = if side.is_buy() {
+        (cost, quantity)
+    } else {
+        (quantity, cost)
+    };
+    if receive_amount < desired_receive {
+        return Err(contract_err(ContractError::NotEnoughLiquidity {}));
+    }
+    let average_price = if quantity.is_zero() {
+        None
+    } else {
+        Some(cost.checked_mul(decimal)? / quantity)
+    };
+
+    Ok(ReverseSimulateResponse {
+        fills,
+        offer_amount,
+        receive_amount,
+        average_price,
+        fee: Uint128::zero(),
+    })
+}
@@ -1,15 +1,128 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::msg::{
+    CircuitBreaker, OrderType, PairStatus, PriceBand, SelfTradePrevention, TokenTransferMode,
+    TrailingStop, TriggerType,
+};
 use classic_terrapexc::trading::{PairInfo, TraderRecord};
-use cosmwasm_std::CanonicalAddr;
-use cw_storage_plus::{Item, Map};
+use cosmwasm_std::{Addr, CanonicalAddr, Uint128};
+use cw_storage_plus::{Item, Map, U64Key, U8Key};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Config {
     pub owner: CanonicalAddr,
     pub pair_list: Vec<PairInfo>,
     pub enabled: bool,
+    // settle fills on internal balances instead of TransferFrom
+    #[serde(default)]
+    pub ledger: bool,
+    // receives surplus swept by Reconcile
+    #[serde(default)]
+    pub treasury: Option<CanonicalAddr>,
+    // factory mode, AddPair instantiates an orderbook contract per pair from this code
+    #[serde(default)]
+    pub pair_code_id: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TriggerOrder {
+    pub order: TraderRecord,
+    pub trigger_type: TriggerType,
+    pub trigger_price: Uint128,
+    pub order_type: OrderType,
+    #[serde(default)]
+    pub trailing: Option<TrailingStop>,
+    // placement order among the pair's triggers firing at the same price
+    #[serde(default)]
+    pub sequence: u64,
+}
+
+// OCO pair of a take profit limit leg and a stop leg, optionally spawned by a bracket entry
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OrderGroup {
+    pub address: Addr,
+    pub pair_id: Uint128,
+    // side of the exit legs, the entry sits on the other side
+    pub is_buy: bool,
+    pub limit_id: String,
+    pub stop_id: String,
+    pub entry_id: Option<String>,
+    pub take_profit_price: Uint128,
+    pub stop_price: Uint128,
+    pub stop_order_type: OrderType,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct PairSettings {
+    pub status: PairStatus,
+    #[serde(default)]
+    pub self_trade_prevention: SelfTradePrevention,
+    #[serde(default)]
+    pub price_band: Option<PriceBand>,
+    #[serde(default)]
+    pub circuit_breaker: Option<CircuitBreaker>,
+    #[serde(default)]
+    pub token_transfer_mode: TokenTransferMode,
+}
+
+// price history of a pair, fed by every fill
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct PairMarket {
+    // sum of price * seconds the price was the last traded one
+    pub cumulative_price: Uint128,
+    pub last_fill_time: u64,
+    pub twap_snapshot_time: u64,
+    pub twap_snapshot_cumulative: Uint128,
+    pub twap: Option<Uint128>,
+    // circuit breaker window
+    pub window_start: u64,
+    pub window_open_price: Uint128,
+    pub halted_until: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct LedgerBalance {
+    pub free: Uint128,
+    pub locked: Uint128,
+}
+
+// funds a resting order holds in the ledger
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OrderLock {
+    pub asset: String,
+    pub amount: Uint128,
+}
+
+// a maker payout in flight, kept until the submessages of the execution ran
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingPayout {
+    pub recipient: Addr,
+    pub token: Addr,
+    pub amount: Uint128,
+}
+
+// a pulled CW20 deposit in flight, with what the contract held before the transfer
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingDeposit {
+    pub address: Addr,
+    pub token: Addr,
+    pub holdings: Uint128,
+}
+
+// hidden part of an iceberg order, the visible slice rests in the book
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct IcebergReserve {
+    pub display_quantity: Uint128,
+    pub hidden_quantity: Uint128,
+}
+
+// where indexing the book placed before the price index resumes: the buy side, the sell
+// side, then the triggers, after the raw key of the last record indexed
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct IndexMigration {
+    pub stage: u8,
+    pub start_after: Option<Vec<u8>>,
 }
 
 pub const CONFIG_KEY: &str = "config";
@@ -17,3 +130,54 @@ pub const CONFIG: Item<Config> = Item::new("config");
 
 pub const BUYERS: Map<String, TraderRecord> = Map::new("buyers");
 pub const SELLERS: Map<String, TraderRecord> = Map::new("sellers");
+
+// time priority of resting orders per side, lower is older, shared with the sequence of triggers
+pub const ORDER_PRIORITY: Map<(U8Key, String), u64> = Map::new("order_priority");
+pub const PRIORITY_SEQ: Item<u64> = Item::new("priority_seq");
+// resting order ids per pair and side, best price first and oldest first within a price
+pub const ORDER_INDEX: Map<(U64Key, U8Key, Vec<u8>), String> = Map::new("order_index");
+// resting orders of every trader by side and id, with the pair they rest on
+pub const ORDERS_OF: Map<(Addr, U8Key, String), Uint128> = Map::new("orders_of");
+pub const ICEBERGS: Map<(U8Key, String), IcebergReserve> = Map::new("icebergs");
+
+// trigger orders grouped by pair, keyed by order id
+pub const TRIGGERS: Map<(U64Key, String), TriggerOrder> = Map::new("triggers");
+// trigger order ids per pair and the direction the price has to move to fire them,
+// in the order they fire, keyed by trigger price and sequence
+pub const TRIGGER_INDEX: Map<(U64Key, U8Key, Vec<u8>), String> = Map::new("trigger_index");
+// trailing stops per pair and side ordered by their mark, keyed by mark and order id
+pub const TRAILING_STOPS: Map<(U64Key, U8Key, Vec<u8>), String> = Map::new("trailing_stops");
+// order groups keyed by group id, and the group id of every member order
+pub const ORDER_GROUPS: Map<String, OrderGroup> = Map::new("order_groups");
+pub const GROUP_OF: Map<String, String> = Map::new("group_of");
+pub const PAIR_SETTINGS: Map<U64Key, PairSettings> = Map::new("pair_settings");
+pub const PAIR_MARKETS: Map<U64Key, PairMarket> = Map::new("pair_markets");
+// ledger balances per trader and asset, and the locks of resting orders keyed by owner, side and id
+pub const BALANCES: Map<(Addr, String), LedgerBalance> = Map::new("balances");
+pub const ORDER_LOCKS: Map<(Addr, U8Key, String), OrderLock> = Map::new("order_locks");
+// quantity of bracket exit legs to lock once the entry fill that spawned them settled
+pub const PENDING_EXIT_LOCKS: Map<(Addr, String), Uint128> = Map::new("pending_exit_locks");
+// payouts of the current execution indexed by reply id, and failed ones left to claim
+pub const PENDING_PAYOUTS: Item<Vec<PendingPayout>> = Item::new("pending_payouts");
+pub const PENDING_DEPOSIT: Item<PendingDeposit> = Item::new("pending_deposit");
+pub const CLAIMS: Map<(Addr, String), Uint128> = Map::new("claims");
+// ledger balances and claims of all traders summed per asset
+pub const LIABILITIES: Map<String, Uint128> = Map::new("liabilities");
+// hook contracts per trader, and the ones notified about every order
+pub const TRADER_HOOKS: Map<Addr, Vec<Addr>> = Map::new("trader_hooks");
+pub const GLOBAL_HOOKS: Item<Vec<Addr>> = Item::new("global_hooks");
+// orderbook contracts of pairs added in factory mode, and the pair awaiting its contract
+pub const PAIR_CONTRACTS: Map<U64Key, Addr> = Map::new("pair_contracts");
+pub const PENDING_PAIR: Item<Uint128> = Item::new("pending_pair");
+// price of the last fill per pair
+pub const LAST_PRICES: Map<U64Key, Uint128> = Map::new("last_prices");
+// indexing left by a migration, trading waits until it is done
+pub const INDEX_MIGRATION: Item<IndexMigration> = Item::new("index_migration");
+
+pub fn pair_key(pair_id: Uint128) -> U64Key {
+    U64Key::new(pair_id.u128() as u64)
+}
+
+pub fn side_key(is_buy: bool) -> U8Key {
+    U8Key::new(is_buy as u8)
+}
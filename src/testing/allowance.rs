@@ -0,0 +1,65 @@
+use cosmwasm_std::{from_binary, Uint128};
+use cw20::Expiration;
+
+use crate::error::ContractError;
+use crate::msg::{CounterOrder, ExecuteMsg, MatchStatus, OrderResponse};
+use crate::testing::mock_querier::mock_dependencies;
+use crate::testing::{order, place, price, resting, run, setup, take, token_pairs};
+
+#[test]
+fn makers_without_a_live_allowance_are_evicted() {
+    let mut deps = mock_dependencies();
+    setup(deps.as_mut(), token_pairs());
+    deps.querier.with_token_balances(&[
+        ("usdt", &[("dave", 40)]),
+        ("lunt", &[("carol", 10), ("erin", 10)]),
+    ]);
+    place(deps.as_mut(), &order("c1", "carol", 0, false, price(2), 10));
+    place(deps.as_mut(), &order("e1", "erin", 0, false, price(2), 10));
+    // erin's approval is replaced by one that expired at height 1
+    deps.querier
+        .with_allowance("lunt", "erin", 10, Expiration::AtHeight(1));
+
+    // the buy costs exactly dave's balance
+    let taker = order("d1", "dave", 0, true, price(2), 20);
+    let res = take(deps.as_mut(), &taker, &["e1", "c1"], None);
+    let data: OrderResponse = from_binary(res.data.as_ref().unwrap()).unwrap();
+    assert_eq!(data.outcomes[0].status, MatchStatus::Evicted);
+    assert_eq!(data.outcomes[1].status, MatchStatus::Filled);
+    assert!(res.events.iter().any(|event| {
+        event.ty == "order_cancelled"
+            && event
+                .attributes
+                .iter()
+                .any(|attr| attr.key == "reason" && attr.value == "evicted")
+    }));
+    assert!(resting(deps.as_ref(), false).is_empty());
+}
+
+#[test]
+fn taker_needs_an_allowance_for_its_size() {
+    let mut deps = mock_dependencies();
+    setup(deps.as_mut(), token_pairs());
+    deps.querier
+        .with_token_balances(&[("usdt", &[("dave", 100)]), ("lunt", &[("carol", 10)])]);
+    deps.querier
+        .with_allowance("usdt", "dave", 19, Expiration::Never {});
+    place(deps.as_mut(), &order("c1", "carol", 0, false, price(2), 10));
+
+    let err = run(
+        deps.as_mut(),
+        "dave",
+        ExecuteMsg::Order {
+            order: order("d1", "dave", 0, true, price(2), 10),
+            add_order: None,
+            update_order: Some(CounterOrder {
+                order_id: "c1".to_string(),
+                quantity: Uint128::new(5),
+            }),
+            remove_orders: None,
+            self_trade_prevention: None,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::InsufficientAllowance {});
+}
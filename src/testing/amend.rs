@@ -0,0 +1,116 @@
+use cosmwasm_std::testing::mock_env;
+use cosmwasm_std::{from_binary, Deps, DepsMut, Response, Uint128};
+
+use crate::contract::query;
+use crate::error::ContractError;
+use crate::msg::{ExecuteMsg, OrderType, QueryMsg, Side, SimulateOrderResponse};
+use crate::testing::mock_querier::mock_dependencies;
+use crate::testing::{balance, deposit, order, place, price, run, setup_ledger};
+
+fn amend(
+    deps: DepsMut,
+    order_id: &str,
+    new_price: Option<Uint128>,
+    new_quantity: Option<u128>,
+) -> Result<Response, ContractError> {
+    run(
+        deps,
+        "alice",
+        ExecuteMsg::AmendOrder {
+            order_id: order_id.to_string(),
+            new_price,
+            new_quantity: new_quantity.map(Uint128::new),
+        },
+    )
+}
+
+// ids of the resting sells in the order a market buy would take them
+fn sell_queue(deps: Deps) -> Vec<String> {
+    let res: SimulateOrderResponse = from_binary(
+        &query(
+            deps,
+            mock_env(),
+            QueryMsg::SimulateOrder {
+                pair_id: Uint128::zero(),
+                side: Side::Buy,
+                quantity: Uint128::new(1000),
+                price: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    res.fills.into_iter().map(|fill| fill.order_id).collect()
+}
+
+#[test]
+fn amending_keeps_priority_only_when_shrinking() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "alice", "uluna", 100);
+    deposit(deps.as_mut(), "carol", "uluna", 50);
+    place(deps.as_mut(), &order("a1", "alice", 0, false, price(2), 50));
+    place(deps.as_mut(), &order("c1", "carol", 0, false, price(2), 50));
+    assert_eq!(sell_queue(deps.as_ref()), vec!["a1", "c1"]);
+
+    let res = amend(deps.as_mut(), "a1", None, Some(30)).unwrap();
+    let amended = res
+        .events
+        .iter()
+        .find(|event| event.ty == "order_amended")
+        .unwrap();
+    assert!(amended
+        .attributes
+        .iter()
+        .any(|attr| attr.key == "quantity" && attr.value == "30"));
+    assert_eq!(sell_queue(deps.as_ref()), vec!["a1", "c1"]);
+    assert_eq!(balance(deps.as_ref(), "alice", "uluna"), (70, 30));
+
+    amend(deps.as_mut(), "a1", None, Some(40)).unwrap();
+    assert_eq!(sell_queue(deps.as_ref()), vec!["c1", "a1"]);
+    assert_eq!(balance(deps.as_ref(), "alice", "uluna"), (60, 40));
+
+    // a better price goes to the front of the book
+    amend(deps.as_mut(), "a1", Some(price(1)), None).unwrap();
+    assert_eq!(sell_queue(deps.as_ref()), vec!["a1", "c1"]);
+
+    // a bigger commitment than the balance covers is refused
+    amend(deps.as_mut(), "a1", None, Some(101)).unwrap_err();
+}
+
+#[test]
+fn only_own_plain_orders_are_amended() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "alice", "uluna", 300);
+    deposit(deps.as_mut(), "carol", "uluna", 50);
+    place(deps.as_mut(), &order("c1", "carol", 0, false, price(2), 50));
+    run(
+        deps.as_mut(),
+        "alice",
+        ExecuteMsg::PlaceIceberg {
+            order: order("i", "alice", 0, false, price(2), 100),
+            display_quantity: Uint128::new(10),
+        },
+    )
+    .unwrap();
+    run(
+        deps.as_mut(),
+        "alice",
+        ExecuteMsg::PlaceOco {
+            limit_order: order("tp", "alice", 0, false, price(3), 100),
+            stop_order: order("sl", "alice", 0, false, Uint128::zero(), 100),
+            stop_price: price(1),
+            stop_order_type: OrderType::Market,
+        },
+    )
+    .unwrap();
+
+    amend(deps.as_mut(), "c1", None, Some(10)).unwrap_err();
+    let err = amend(deps.as_mut(), "i", None, Some(5)).unwrap_err();
+    assert_eq!(err, ContractError::InvalidInput {});
+    let err = amend(deps.as_mut(), "tp", Some(price(4)), None).unwrap_err();
+    assert_eq!(err, ContractError::InvalidInput {});
+    let err = amend(deps.as_mut(), "tp", None, None).unwrap_err();
+    assert_eq!(err, ContractError::InvalidInput {});
+}
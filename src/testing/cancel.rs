@@ -0,0 +1,132 @@
+use cosmwasm_std::Uint128;
+
+use crate::error::ContractError;
+use crate::msg::{ExecuteMsg, OrderType, PairStatus, TriggerType};
+use crate::testing::mock_querier::mock_dependencies;
+use crate::testing::{
+    balance, deposit, has_attribute, order, place, price, resting, run, set_status, setup_ledger,
+    triggers, OWNER,
+};
+
+#[test]
+fn cancel_orders_skips_pairs_refusing_cancels() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "alice", "uluna", 10);
+    deposit(deps.as_mut(), "alice", "umnt", 10);
+    place(deps.as_mut(), &order("a1", "alice", 0, false, price(2), 10));
+    place(deps.as_mut(), &order("h1", "alice", 1, false, price(2), 10));
+    set_status(deps.as_mut(), 1, PairStatus::Halted);
+
+    let res = run(
+        deps.as_mut(),
+        "alice",
+        ExecuteMsg::CancelOrders {
+            order_ids: vec!["a1".to_string(), "h1".to_string(), "x".to_string()],
+        },
+    )
+    .unwrap();
+    assert!(has_attribute(&res, "skipped", "h1"));
+    assert_eq!(resting(deps.as_ref(), false), vec![("h1".to_string(), 10)]);
+    assert_eq!(balance(deps.as_ref(), "alice", "uluna"), (10, 0));
+    assert_eq!(balance(deps.as_ref(), "alice", "umnt"), (0, 10));
+}
+
+#[test]
+fn cancel_all_pages_past_pairs_refusing_cancels() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "alice", "uluna", 30);
+    deposit(deps.as_mut(), "alice", "umnt", 10);
+    deposit(deps.as_mut(), "carol", "uluna", 10);
+    for id in ["a1", "a2", "a3"] {
+        place(deps.as_mut(), &order(id, "alice", 0, false, price(2), 10));
+    }
+    place(deps.as_mut(), &order("c1", "carol", 0, false, price(2), 10));
+    place(deps.as_mut(), &order("h1", "alice", 1, false, price(2), 10));
+    set_status(deps.as_mut(), 1, PairStatus::Halted);
+
+    let cancel_all = ExecuteMsg::CancelAll {
+        pair_id: None,
+        limit: Some(2),
+    };
+    let res = run(deps.as_mut(), "alice", cancel_all.clone()).unwrap();
+    assert!(has_attribute(&res, "removed", "2"));
+    assert!(has_attribute(&res, "done", "false"));
+
+    let res = run(deps.as_mut(), "alice", cancel_all).unwrap();
+    assert!(has_attribute(&res, "removed", "1"));
+    assert!(has_attribute(&res, "skipped", "h1"));
+    assert!(has_attribute(&res, "done", "true"));
+    assert_eq!(
+        resting(deps.as_ref(), false),
+        vec![("c1".to_string(), 10), ("h1".to_string(), 10)]
+    );
+    assert_eq!(balance(deps.as_ref(), "alice", "uluna"), (30, 0));
+}
+
+#[test]
+fn cancel_all_of_one_pair() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "alice", "uluna", 10);
+    deposit(deps.as_mut(), "alice", "umnt", 10);
+    place(deps.as_mut(), &order("a1", "alice", 0, false, price(2), 10));
+    place(deps.as_mut(), &order("m1", "alice", 1, false, price(2), 10));
+
+    let res = run(
+        deps.as_mut(),
+        "alice",
+        ExecuteMsg::CancelAll {
+            pair_id: Some(Uint128::new(1)),
+            limit: None,
+        },
+    )
+    .unwrap();
+    assert!(has_attribute(&res, "removed", "1"));
+    assert_eq!(resting(deps.as_ref(), false), vec![("a1".to_string(), 10)]);
+}
+
+#[test]
+fn force_cancel_winds_down_one_pair() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "alice", "uluna", 30);
+    deposit(deps.as_mut(), "alice", "umnt", 10);
+    deposit(deps.as_mut(), "bob", "uusd", 20);
+    place(deps.as_mut(), &order("a1", "alice", 0, false, price(3), 10));
+    place(deps.as_mut(), &order("b1", "bob", 0, true, price(1), 10));
+    place(deps.as_mut(), &order("m1", "alice", 1, false, price(2), 10));
+    run(
+        deps.as_mut(),
+        "alice",
+        ExecuteMsg::PlaceTrigger {
+            order: order("s1", "alice", 0, false, Uint128::zero(), 10),
+            trigger_type: TriggerType::StopLoss,
+            trigger_price: price(1),
+            order_type: OrderType::Market,
+        },
+    )
+    .unwrap();
+
+    let force_cancel = ExecuteMsg::ForceCancel {
+        pair_id: Uint128::zero(),
+        limit: Some(2),
+    };
+    let err = run(deps.as_mut(), "alice", force_cancel.clone()).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let res = run(deps.as_mut(), OWNER, force_cancel.clone()).unwrap();
+    assert!(has_attribute(&res, "removed", "2"));
+    assert!(has_attribute(&res, "done", "false"));
+    assert!(resting(deps.as_ref(), true).is_empty());
+    assert_eq!(resting(deps.as_ref(), false), vec![("m1".to_string(), 10)]);
+
+    let res = run(deps.as_mut(), OWNER, force_cancel).unwrap();
+    assert!(has_attribute(&res, "removed", "1"));
+    assert!(has_attribute(&res, "done", "true"));
+    assert!(triggers(deps.as_ref(), 0).is_empty());
+    assert_eq!(balance(deps.as_ref(), "alice", "uluna"), (30, 0));
+    assert_eq!(balance(deps.as_ref(), "alice", "umnt"), (0, 10));
+    assert_eq!(balance(deps.as_ref(), "bob", "uusd"), (20, 0));
+}
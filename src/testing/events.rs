@@ -0,0 +1,91 @@
+use cosmwasm_std::{Event, Response, Uint128};
+
+use crate::events::EVENT_SCHEMA_VERSION;
+use crate::msg::{ExecuteMsg, PairStatus};
+use crate::testing::mock_querier::mock_dependencies;
+use crate::testing::{deposit, fill, order, place, price, run, setup_ledger, OWNER};
+
+fn event<'a>(res: &'a Response, ty: &str) -> &'a Event {
+    res.events.iter().find(|event| event.ty == ty).unwrap()
+}
+
+fn value(event: &Event, key: &str) -> String {
+    event
+        .attributes
+        .iter()
+        .find(|attr| attr.key == key)
+        .map(|attr| attr.value.clone())
+        .unwrap()
+}
+
+#[test]
+fn order_lifecycle_events() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "alice", "uluna", 100);
+    deposit(deps.as_mut(), "bob", "uusd", 100);
+
+    let res = place(deps.as_mut(), &order("a1", "alice", 0, false, price(2), 50));
+    let placed = event(&res, "order_placed");
+    assert_eq!(value(placed, "schema_version"), EVENT_SCHEMA_VERSION);
+    assert_eq!(value(placed, "order_id"), "a1");
+    assert_eq!(value(placed, "side"), "sell");
+    assert_eq!(value(placed, "quantity"), "50");
+    assert_eq!(value(placed, "kind"), "book");
+
+    // a taker priced above the maker fills at the maker's price
+    let res = fill(
+        deps.as_mut(),
+        &order("b1", "bob", 0, true, price(3), 20),
+        "a1",
+    );
+    let filled = event(&res, "order_filled");
+    assert_eq!(value(filled, "pair_id"), "0");
+    assert_eq!(value(filled, "maker_order_id"), "a1");
+    assert_eq!(value(filled, "maker"), "alice");
+    assert_eq!(value(filled, "taker_order_id"), "b1");
+    assert_eq!(value(filled, "taker"), "bob");
+    assert_eq!(value(filled, "taker_side"), "buy");
+    assert_eq!(value(filled, "price"), price(2).to_string());
+    assert_eq!(value(filled, "quantity"), "20");
+    assert_eq!(value(filled, "maker_fee"), "0");
+    assert_eq!(value(filled, "taker_fee"), "0");
+
+    let res = run(
+        deps.as_mut(),
+        "alice",
+        ExecuteMsg::Cancel {
+            order_id: "a1".to_string(),
+            is_buy: false,
+        },
+    )
+    .unwrap();
+    let cancelled = event(&res, "order_cancelled");
+    assert_eq!(value(cancelled, "order_id"), "a1");
+    assert_eq!(value(cancelled, "quantity"), "30");
+    assert_eq!(value(cancelled, "reason"), "user");
+}
+
+#[test]
+fn pair_updates_are_published() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+
+    let res = run(
+        deps.as_mut(),
+        OWNER,
+        ExecuteMsg::UpdatePair {
+            pair_id: Uint128::new(1),
+            status: Some(PairStatus::PostOnly),
+            self_trade_prevention: None,
+            price_band: None,
+            circuit_breaker: None,
+            token_transfer_mode: None,
+        },
+    )
+    .unwrap();
+    let updated = event(&res, "pair_updated");
+    assert_eq!(value(updated, "pair_id"), "1");
+    assert_eq!(value(updated, "status"), "PostOnly");
+    assert_eq!(value(updated, "price_band"), "false");
+}
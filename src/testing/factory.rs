@@ -0,0 +1,117 @@
+use cosmwasm_std::testing::mock_env;
+use cosmwasm_std::{
+    from_binary, to_binary, ContractResult, CosmosMsg, Reply, SubMsg, SubMsgExecutionResponse,
+    Uint128, WasmMsg,
+};
+use protobuf::Message;
+
+use crate::contract::{query, reply};
+use crate::error::ContractError;
+use crate::factory::INSTANTIATE_PAIR_REPLY_ID;
+use crate::msg::{ExecuteMsg, PairResponse, QueryMsg};
+use crate::response::MsgInstantiateContractResponse;
+use crate::testing::mock_querier::mock_dependencies;
+use crate::testing::{native, native_pairs, order, price, run, setup, OWNER};
+use classic_terrapexc::trading::PairInfo;
+
+#[test]
+fn added_pairs_get_their_own_contract() {
+    let mut deps = mock_dependencies();
+    setup(deps.as_mut(), native_pairs());
+    run(
+        deps.as_mut(),
+        OWNER,
+        ExecuteMsg::UpdateConfig {
+            owner: None,
+            pair_list: None,
+            enabled: None,
+            ledger: None,
+            treasury: None,
+            pair_code_id: Some(7),
+        },
+    )
+    .unwrap();
+
+    let add_pair = ExecuteMsg::AddPair {
+        pair_info: PairInfo {
+            from_asset: native("uusd"),
+            to_asset: native("ukrw"),
+        },
+    };
+    let err = run(deps.as_mut(), "alice", add_pair.clone()).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    let res = run(deps.as_mut(), OWNER, add_pair.clone()).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(res.messages[0].id, INSTANTIATE_PAIR_REPLY_ID);
+    match &res.messages[0].msg {
+        CosmosMsg::Wasm(WasmMsg::Instantiate { code_id, admin, .. }) => {
+            assert_eq!(*code_id, 7);
+            assert_eq!(admin.as_deref(), Some(OWNER));
+        }
+        _ => panic!("unexpected message"),
+    }
+    // a listed pair is not added twice
+    let err = run(deps.as_mut(), OWNER, add_pair).unwrap_err();
+    assert_eq!(err, ContractError::InvalidInput {});
+
+    let mut instantiated = MsgInstantiateContractResponse::new();
+    instantiated.set_contract_address("pair0002".to_string());
+    let res = reply(
+        deps.as_mut(),
+        mock_env(),
+        Reply {
+            id: INSTANTIATE_PAIR_REPLY_ID,
+            result: ContractResult::Ok(SubMsgExecutionResponse {
+                events: vec![],
+                data: Some(instantiated.write_to_bytes().unwrap().into()),
+            }),
+        },
+    )
+    .unwrap();
+    // the owner of this contract takes over the new one
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(WasmMsg::Execute {
+            contract_addr: "pair0002".to_string(),
+            msg: to_binary(&ExecuteMsg::UpdateConfig {
+                owner: Some(OWNER.to_string()),
+                pair_list: None,
+                enabled: None,
+                ledger: None,
+                treasury: None,
+                pair_code_id: None,
+            })
+            .unwrap(),
+            funds: vec![],
+        })]
+    );
+
+    let pair: PairResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Pair {
+                pair_id: Uint128::new(2),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(pair.contract.unwrap().as_str(), "pair0002");
+
+    // its orders go to its own contract
+    let record = order("a1", "alice", 2, false, price(2), 10);
+    let err = run(
+        deps.as_mut(),
+        "alice",
+        ExecuteMsg::Order {
+            order: record.clone(),
+            add_order: Some(record),
+            update_order: None,
+            remove_orders: None,
+            self_trade_prevention: None,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::PairUnavailable {});
+}
@@ -0,0 +1,118 @@
+use cosmwasm_std::testing::mock_env;
+use cosmwasm_std::{from_binary, Deps, Uint128};
+
+use crate::contract::query;
+use crate::msg::{ExecuteMsg, OrderGroupResponse, OrderType, QueryMsg};
+use crate::testing::mock_querier::mock_dependencies;
+use crate::testing::{balance, deposit, fill, order, price, resting, run, setup_ledger, triggers};
+
+fn group(deps: Deps, group_id: &str) -> OrderGroupResponse {
+    from_binary(
+        &query(
+            deps,
+            mock_env(),
+            QueryMsg::OrderGroup {
+                group_id: group_id.to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap()
+}
+
+#[test]
+fn oco_legs_cancel_each_other() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "alice", "uluna", 100);
+    deposit(deps.as_mut(), "bob", "uusd", 120);
+
+    let place_oco = ExecuteMsg::PlaceOco {
+        limit_order: order("tp", "alice", 0, false, price(3), 100),
+        stop_order: order("sl", "alice", 0, false, Uint128::zero(), 100),
+        stop_price: price(1),
+        stop_order_type: OrderType::Market,
+    };
+    run(deps.as_mut(), "alice", place_oco.clone()).unwrap();
+    let oco = group(deps.as_ref(), "tp");
+    assert_eq!(oco.limit_id, "tp");
+    assert_eq!(oco.stop_id, "sl");
+    assert_eq!(resting(deps.as_ref(), false), vec![("tp".to_string(), 100)]);
+    assert_eq!(triggers(deps.as_ref(), 0), vec![("sl".to_string(), 100)]);
+
+    // only the owner places groups, and leg ids in use are refused
+    run(deps.as_mut(), "bob", place_oco.clone()).unwrap_err();
+    run(deps.as_mut(), "alice", place_oco).unwrap_err();
+
+    // a fill of the take profit reduces the stop by as much
+    fill(
+        deps.as_mut(),
+        &order("b1", "bob", 0, true, price(3), 40),
+        "tp",
+    );
+    assert_eq!(resting(deps.as_ref(), false), vec![("tp".to_string(), 60)]);
+    assert_eq!(triggers(deps.as_ref(), 0), vec![("sl".to_string(), 60)]);
+
+    // cancelling one leg cancels the other
+    run(
+        deps.as_mut(),
+        "alice",
+        ExecuteMsg::Cancel {
+            order_id: "tp".to_string(),
+            is_buy: false,
+        },
+    )
+    .unwrap();
+    assert!(resting(deps.as_ref(), false).is_empty());
+    assert!(triggers(deps.as_ref(), 0).is_empty());
+    assert_eq!(balance(deps.as_ref(), "alice", "uluna"), (60, 0));
+    assert_eq!(balance(deps.as_ref(), "alice", "uusd"), (120, 0));
+}
+
+#[test]
+fn bracket_fills_spawn_the_exit_pair() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "alice", "uusd", 200);
+    deposit(deps.as_mut(), "carol", "uluna", 100);
+
+    let place_bracket = |take_profit_price| ExecuteMsg::PlaceBracket {
+        entry: order("e", "alice", 0, true, price(2), 100),
+        take_profit_price,
+        stop_price: price(1),
+        stop_order_type: OrderType::Market,
+    };
+    // the take profit of a long entry sits above it
+    run(deps.as_mut(), "alice", place_bracket(price(2))).unwrap_err();
+    run(deps.as_mut(), "alice", place_bracket(price(3))).unwrap();
+    assert_eq!(resting(deps.as_ref(), true), vec![("e".to_string(), 100)]);
+    assert!(triggers(deps.as_ref(), 0).is_empty());
+
+    // each entry fill grows the exit legs by the filled quantity
+    fill(
+        deps.as_mut(),
+        &order("c1", "carol", 0, false, price(2), 40),
+        "e",
+    );
+    assert_eq!(
+        resting(deps.as_ref(), false),
+        vec![("e-tp".to_string(), 40)]
+    );
+    assert_eq!(triggers(deps.as_ref(), 0), vec![("e-sl".to_string(), 40)]);
+    fill(
+        deps.as_mut(),
+        &order("c2", "carol", 0, false, price(2), 20),
+        "e",
+    );
+    assert_eq!(
+        resting(deps.as_ref(), false),
+        vec![("e-tp".to_string(), 60)]
+    );
+    assert_eq!(triggers(deps.as_ref(), 0), vec![("e-sl".to_string(), 60)]);
+
+    let bracket = group(deps.as_ref(), "e");
+    assert_eq!(bracket.entry_id, Some("e".to_string()));
+    assert!(!bracket.is_buy);
+    assert_eq!(balance(deps.as_ref(), "alice", "uluna"), (0, 60));
+    assert_eq!(balance(deps.as_ref(), "alice", "uusd"), (0, 80));
+}
@@ -0,0 +1,143 @@
+use cosmwasm_std::{from_binary, Addr, CosmosMsg, DepsMut, ReplyOn, Response, WasmMsg};
+
+use crate::error::ContractError;
+use crate::hooks::{HOOK_GAS_LIMIT, HOOK_REPLY_ID, MAX_HOOKS};
+use crate::msg::{ExecuteMsg, TradeHookExecuteMsg, TradeHookMsg};
+use crate::testing::mock_querier::mock_dependencies;
+use crate::testing::{deposit, fill, has_attribute, order, place, price, run, setup_ledger, OWNER};
+
+fn add_hook(deps: DepsMut, sender: &str, addr: &str) -> Result<Response, ContractError> {
+    run(
+        deps,
+        sender,
+        ExecuteMsg::AddHook {
+            addr: addr.to_string(),
+        },
+    )
+}
+
+// (hook, notifications) of every callback in the response
+fn callbacks(res: &Response) -> Vec<(String, Vec<TradeHookMsg>)> {
+    res.messages
+        .iter()
+        .filter(|sub_msg| sub_msg.id == HOOK_REPLY_ID)
+        .map(|sub_msg| {
+            assert_eq!(sub_msg.reply_on, ReplyOn::Error);
+            assert_eq!(sub_msg.gas_limit, Some(HOOK_GAS_LIMIT));
+            match &sub_msg.msg {
+                CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr, msg, ..
+                }) => {
+                    let TradeHookExecuteMsg::TradeHook(notifications) = from_binary(msg).unwrap();
+                    (contract_addr.clone(), notifications)
+                }
+                _ => panic!("unexpected message"),
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn fills_notify_the_hooks_of_both_sides() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "alice", "uluna", 100);
+    deposit(deps.as_mut(), "bob", "uusd", 100);
+    add_hook(deps.as_mut(), "alice", "alicehook").unwrap();
+    add_hook(deps.as_mut(), "bob", "bobhook").unwrap();
+    let err = add_hook(deps.as_mut(), "bob", "bobhook").unwrap_err();
+    assert_eq!(err, ContractError::HookAlreadyRegistered {});
+
+    place(deps.as_mut(), &order("a1", "alice", 0, false, price(2), 50));
+    let res = fill(
+        deps.as_mut(),
+        &order("b1", "bob", 0, true, price(2), 20),
+        "a1",
+    );
+    let callbacks = callbacks(&res);
+    assert_eq!(callbacks.len(), 2);
+    assert_eq!(callbacks[0].0, "alicehook");
+    assert_eq!(
+        callbacks[0].1,
+        vec![TradeHookMsg::OrderFilled {
+            pair_id: 0u128.into(),
+            order_id: "a1".to_string(),
+            address: Addr::unchecked("alice"),
+            is_buy: false,
+            is_maker: true,
+            price: price(2),
+            quantity: 20u128.into(),
+        }]
+    );
+    assert_eq!(callbacks[1].0, "bobhook");
+}
+
+#[test]
+fn a_counterparty_can_not_crowd_out_other_hooks() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "carol", "uluna", 100);
+    deposit(deps.as_mut(), "alice", "uusd", 100);
+    run(
+        deps.as_mut(),
+        OWNER,
+        ExecuteMsg::AddGlobalHook {
+            addr: "indexer".to_string(),
+        },
+    )
+    .unwrap();
+    for n in 0..MAX_HOOKS {
+        add_hook(deps.as_mut(), "carol", &format!("carol{}", n)).unwrap();
+    }
+    let err = add_hook(deps.as_mut(), "carol", "carol9").unwrap_err();
+    assert_eq!(err, ContractError::TooManyHooks { max: MAX_HOOKS });
+    add_hook(deps.as_mut(), "alice", "vault").unwrap();
+
+    place(deps.as_mut(), &order("c1", "carol", 0, false, price(2), 50));
+    let res = fill(
+        deps.as_mut(),
+        &order("a1", "alice", 0, true, price(2), 20),
+        "c1",
+    );
+    // first hooks of everyone, then carol's further ones while the budget lasts
+    let called: Vec<String> = callbacks(&res).into_iter().map(|(hook, _)| hook).collect();
+    assert_eq!(
+        called,
+        vec!["indexer", "carol0", "vault", "carol1", "carol2"]
+    );
+    assert!(has_attribute(&res, "hooks_skipped", "carol3,carol4"));
+}
+
+#[test]
+fn every_trader_keeps_a_hook_past_the_budget() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "alice", "uusd", 1000);
+    let makers = ["maker0", "maker1", "maker2", "maker3", "maker4", "maker5"];
+    for maker in makers.iter() {
+        deposit(deps.as_mut(), maker, "uluna", 10);
+        add_hook(deps.as_mut(), maker, &format!("{}hook", maker)).unwrap();
+        place(deps.as_mut(), &order(maker, maker, 0, false, price(2), 10));
+    }
+    add_hook(deps.as_mut(), "alice", "vault").unwrap();
+
+    let res = run(
+        deps.as_mut(),
+        "alice",
+        ExecuteMsg::Order {
+            order: order("a1", "alice", 0, true, price(2), 60),
+            add_order: None,
+            update_order: None,
+            remove_orders: Some(makers.iter().map(|maker| maker.to_string()).collect()),
+            self_trade_prevention: None,
+        },
+    )
+    .unwrap();
+    let called: Vec<String> = callbacks(&res).into_iter().map(|(hook, _)| hook).collect();
+    assert_eq!(called.len(), makers.len() + 1);
+    assert!(called.contains(&"vault".to_string()));
+    assert!(!res
+        .attributes
+        .iter()
+        .any(|attr| attr.key == "hooks_skipped"));
+}
@@ -0,0 +1,98 @@
+use cosmwasm_std::{DepsMut, Response, Uint128};
+
+use crate::error::ContractError;
+use crate::msg::ExecuteMsg;
+use crate::testing::mock_querier::mock_dependencies;
+use crate::testing::{balance, deposit, fill, order, price, resting, run, setup_ledger, take};
+
+fn place_iceberg(deps: DepsMut, amount: u128, display: u128) -> Result<Response, ContractError> {
+    run(
+        deps,
+        "alice",
+        ExecuteMsg::PlaceIceberg {
+            order: order("i", "alice", 0, false, price(2), amount),
+            display_quantity: Uint128::new(display),
+        },
+    )
+}
+
+// quantities of the iceberg slices a response shows
+fn slices_shown(res: &Response) -> Vec<String> {
+    res.events
+        .iter()
+        .filter(|event| event.ty == "order_placed")
+        .filter(|event| {
+            event
+                .attributes
+                .iter()
+                .any(|attr| attr.key == "kind" && attr.value == "iceberg")
+        })
+        .flat_map(|event| event.attributes.iter())
+        .filter(|attr| attr.key == "quantity")
+        .map(|attr| attr.value.clone())
+        .collect()
+}
+
+#[test]
+fn iceberg_display_must_hide_something() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "alice", "uluna", 100);
+
+    let err = place_iceberg(deps.as_mut(), 100, 100).unwrap_err();
+    assert_eq!(err, ContractError::InvalidInput {});
+    let err = place_iceberg(deps.as_mut(), 100, 0).unwrap_err();
+    assert_eq!(err, ContractError::InvalidZeroAmount {});
+}
+
+#[test]
+fn iceberg_shows_one_slice_at_a_time() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "alice", "uluna", 100);
+    deposit(deps.as_mut(), "bob", "uusd", 200);
+
+    let res = place_iceberg(deps.as_mut(), 70, 30).unwrap();
+    assert_eq!(slices_shown(&res), vec!["30"]);
+    assert_eq!(resting(deps.as_ref(), false), vec![("i".to_string(), 30)]);
+    // the whole order is locked, hidden part included
+    assert_eq!(balance(deps.as_ref(), "alice", "uluna"), (30, 70));
+
+    // a partial fill of the slice shows nothing new
+    let res = fill(
+        deps.as_mut(),
+        &order("b1", "bob", 0, true, price(2), 10),
+        "i",
+    );
+    assert!(slices_shown(&res).is_empty());
+    assert_eq!(resting(deps.as_ref(), false), vec![("i".to_string(), 20)]);
+
+    // taking the rest of the slice places the next one
+    let res = take(
+        deps.as_mut(),
+        &order("b2", "bob", 0, true, price(2), 20),
+        &["i"],
+        None,
+    );
+    assert_eq!(slices_shown(&res), vec!["30"]);
+    assert_eq!(resting(deps.as_ref(), false), vec![("i".to_string(), 30)]);
+
+    // the last slice is what is left of the reserve
+    let res = take(
+        deps.as_mut(),
+        &order("b3", "bob", 0, true, price(2), 30),
+        &["i"],
+        None,
+    );
+    assert_eq!(slices_shown(&res), vec!["10"]);
+    let res = take(
+        deps.as_mut(),
+        &order("b4", "bob", 0, true, price(2), 10),
+        &["i"],
+        None,
+    );
+    assert!(slices_shown(&res).is_empty());
+    assert!(resting(deps.as_ref(), false).is_empty());
+    assert_eq!(balance(deps.as_ref(), "alice", "uluna"), (30, 0));
+    assert_eq!(balance(deps.as_ref(), "alice", "uusd"), (140, 0));
+}
@@ -0,0 +1,265 @@
+use cosmwasm_std::testing::{mock_env, mock_info};
+use cosmwasm_std::{
+    coins, from_binary, to_binary, BankMsg, ContractResult, CosmosMsg, Deps, Reply, ReplyOn,
+    SubMsg, SubMsgExecutionResponse, Uint128,
+};
+use cw20::Cw20ReceiveMsg;
+
+use crate::contract::{execute, query, reply};
+use crate::error::ContractError;
+use crate::ledger::DEPOSIT_REPLY_ID;
+use crate::msg::{
+    AssetSolvency, Cw20HookMsg, ExecuteMsg, LastPriceResponse, MatchStatus, OrderResponse,
+    QueryMsg, SolvencyResponse, TokenTransferMode,
+};
+use crate::testing::mock_querier::mock_dependencies;
+use crate::testing::{
+    balance, deposit, native, native_pairs, order, place, price, resting, run, setup, setup_ledger,
+    take, token_pairs, OWNER,
+};
+
+#[test]
+fn ledger_deposit_and_withdraw() {
+    let mut deps = mock_dependencies();
+    setup(deps.as_mut(), native_pairs());
+
+    // nothing is credited while the ledger is off
+    let info = mock_info("alice", &coins(1000, "uusd"));
+    let err = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Deposit {}).unwrap_err();
+    assert_eq!(err, ContractError::Disabled {});
+
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "alice", "uusd", 1000);
+    assert_eq!(balance(deps.as_ref(), "alice", "uusd"), (1000, 0));
+
+    // only listed assets are accepted
+    let info = mock_info("alice", &coins(1000, "ukrw"));
+    execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Deposit {}).unwrap_err();
+
+    // a resting buy locks its cost, which can not be withdrawn
+    place(deps.as_mut(), &order("a1", "alice", 0, true, price(2), 300));
+    assert_eq!(balance(deps.as_ref(), "alice", "uusd"), (400, 600));
+    let err = run(
+        deps.as_mut(),
+        "alice",
+        ExecuteMsg::Withdraw {
+            asset: native("uusd"),
+            amount: Uint128::new(401),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::InsufficientFunds {});
+
+    let res = run(
+        deps.as_mut(),
+        "alice",
+        ExecuteMsg::Withdraw {
+            asset: native("uusd"),
+            amount: Uint128::new(400),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "alice".to_string(),
+            amount: coins(400, "uusd"),
+        }))]
+    );
+    assert_eq!(balance(deps.as_ref(), "alice", "uusd"), (0, 600));
+
+    // cancelling frees the lock again
+    run(
+        deps.as_mut(),
+        "alice",
+        ExecuteMsg::Cancel {
+            order_id: "a1".to_string(),
+            is_buy: true,
+        },
+    )
+    .unwrap();
+    assert_eq!(balance(deps.as_ref(), "alice", "uusd"), (600, 0));
+}
+
+#[test]
+fn ledger_fill_moves_balances() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "bob", "uluna", 100);
+    deposit(deps.as_mut(), "alice", "uusd", 500);
+
+    place(deps.as_mut(), &order("b1", "bob", 0, false, price(2), 100));
+    let res = take(
+        deps.as_mut(),
+        &order("a1", "alice", 0, true, price(3), 100),
+        &["b1"],
+        None,
+    );
+    let data: OrderResponse = from_binary(res.data.as_ref().unwrap()).unwrap();
+    assert_eq!(data.outcomes[0].status, MatchStatus::Filled);
+    // fills settle at the maker's price
+    assert_eq!(data.outcomes[0].price, Some(price(2)));
+
+    assert_eq!(balance(deps.as_ref(), "alice", "uusd"), (300, 0));
+    assert_eq!(balance(deps.as_ref(), "alice", "uluna"), (100, 0));
+    assert_eq!(balance(deps.as_ref(), "bob", "uusd"), (200, 0));
+    assert_eq!(balance(deps.as_ref(), "bob", "uluna"), (0, 0));
+    assert!(resting(deps.as_ref(), false).is_empty());
+
+    let res: LastPriceResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::LastPrice {
+                pair_id: Uint128::zero(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.price, Some(price(2)));
+}
+
+fn solvency(deps: Deps, asset: &str) -> AssetSolvency {
+    let res: SolvencyResponse =
+        from_binary(&query(deps, mock_env(), QueryMsg::Solvency {}).unwrap()).unwrap();
+    res.assets
+        .into_iter()
+        .find(|solvency| solvency.asset == asset)
+        .unwrap()
+}
+
+#[test]
+fn reconcile_sweeps_only_the_surplus() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "alice", "uusd", 100);
+    place(deps.as_mut(), &order("a1", "alice", 0, true, price(2), 25));
+    // 30 more than alice deposited were sent by mistake
+    let contract = mock_env().contract.address;
+    deps.querier
+        .with_balance(contract.as_str(), coins(130, "uusd"));
+
+    // locked order funds are owed as much as free balance
+    let uusd = solvency(deps.as_ref(), "uusd");
+    assert_eq!(uusd.liabilities, Uint128::new(100));
+    assert_eq!(uusd.holdings, Uint128::new(130));
+    assert!(uusd.solvent);
+    assert!(solvency(deps.as_ref(), "uluna").solvent);
+
+    let reconcile = ExecuteMsg::Reconcile {
+        asset: native("uusd"),
+    };
+    let err = run(deps.as_mut(), "alice", reconcile.clone()).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    // there is nowhere to sweep to until a treasury is set
+    let err = run(deps.as_mut(), OWNER, reconcile.clone()).unwrap_err();
+    assert_eq!(err, ContractError::InvalidInput {});
+    run(
+        deps.as_mut(),
+        OWNER,
+        ExecuteMsg::UpdateConfig {
+            owner: None,
+            pair_list: None,
+            enabled: None,
+            ledger: None,
+            treasury: Some("treasury".to_string()),
+            pair_code_id: None,
+        },
+    )
+    .unwrap();
+
+    let res = run(deps.as_mut(), OWNER, reconcile.clone()).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "treasury".to_string(),
+            amount: coins(30, "uusd"),
+        }))]
+    );
+
+    // once holdings fall short the asset is reported insolvent
+    deps.querier
+        .with_balance(contract.as_str(), coins(90, "uusd"));
+    assert!(!solvency(deps.as_ref(), "uusd").solvent);
+    let err = run(deps.as_mut(), OWNER, reconcile).unwrap_err();
+    assert_eq!(err, ContractError::InvalidZeroAmount {});
+}
+
+#[test]
+fn pulled_deposits_credit_what_arrived() {
+    let mut deps = mock_dependencies();
+    setup(deps.as_mut(), token_pairs());
+    run(
+        deps.as_mut(),
+        OWNER,
+        ExecuteMsg::UpdateConfig {
+            owner: None,
+            pair_list: None,
+            enabled: None,
+            ledger: Some(true),
+            treasury: None,
+            pair_code_id: None,
+        },
+    )
+    .unwrap();
+    let transfer_mode = |mode| ExecuteMsg::UpdatePair {
+        pair_id: Uint128::zero(),
+        status: None,
+        self_trade_prevention: None,
+        price_band: None,
+        circuit_breaker: None,
+        token_transfer_mode: Some(mode),
+    };
+    run(
+        deps.as_mut(),
+        OWNER,
+        transfer_mode(TokenTransferMode::FeeOnTransfer),
+    )
+    .unwrap();
+    let contract = mock_env().contract.address.to_string();
+    deps.querier
+        .with_token_balances(&[("lunt", &[(contract.as_str(), 50), ("alice", 100)])]);
+
+    // a sent amount can not be trusted for such a token
+    let receive = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "alice".to_string(),
+        amount: Uint128::new(100),
+        msg: to_binary(&Cw20HookMsg::Deposit {}).unwrap(),
+    });
+    let info = mock_info("lunt", &[]);
+    let err = execute(deps.as_mut(), mock_env(), info, receive).unwrap_err();
+    assert_eq!(err, ContractError::UnacceptableToken {});
+
+    let deposit_token = ExecuteMsg::DepositToken {
+        token: "lunt".to_string(),
+        amount: Uint128::new(100),
+    };
+    let res = run(deps.as_mut(), "alice", deposit_token.clone()).unwrap();
+    assert_eq!(res.messages[0].id, DEPOSIT_REPLY_ID);
+    assert_eq!(res.messages[0].reply_on, ReplyOn::Success);
+
+    // the token kept 2 of the 100 pulled
+    deps.querier
+        .with_token_balances(&[("lunt", &[(contract.as_str(), 148), ("alice", 0)])]);
+    let transferred = Reply {
+        id: DEPOSIT_REPLY_ID,
+        result: ContractResult::Ok(SubMsgExecutionResponse {
+            events: vec![],
+            data: None,
+        }),
+    };
+    reply(deps.as_mut(), mock_env(), transferred).unwrap();
+    assert_eq!(balance(deps.as_ref(), "alice", "lunt"), (98, 0));
+
+    // a rejected token is not taken in at all
+    run(
+        deps.as_mut(),
+        OWNER,
+        transfer_mode(TokenTransferMode::Rejected),
+    )
+    .unwrap();
+    let err = run(deps.as_mut(), "alice", deposit_token).unwrap_err();
+    assert_eq!(err, ContractError::UnacceptableToken {});
+}
@@ -0,0 +1,142 @@
+use cosmwasm_std::{from_binary, Response, Uint128};
+
+use crate::error::ContractError;
+use crate::msg::{CounterOrder, ExecuteMsg, MatchOutcome, MatchStatus, OrderResponse};
+use crate::testing::mock_querier::mock_dependencies;
+use crate::testing::{
+    balance, deposit, fill, order, place, price, resting, run, setup, setup_ledger, take,
+    token_pairs,
+};
+use classic_terrapexc::trading::TraderRecord;
+
+fn outcomes(res: &Response) -> Vec<MatchOutcome> {
+    let data: OrderResponse = from_binary(res.data.as_ref().unwrap()).unwrap();
+    data.outcomes
+}
+
+#[test]
+fn counter_orders_must_cross_and_fill_at_the_maker_price() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "carol", "uluna", 20);
+    deposit(deps.as_mut(), "carol", "umnt", 10);
+    deposit(deps.as_mut(), "bob", "uusd", 100);
+    deposit(deps.as_mut(), "dave", "uusd", 100);
+    place(deps.as_mut(), &order("c1", "carol", 0, false, price(2), 10));
+    place(deps.as_mut(), &order("c2", "carol", 0, false, price(4), 10));
+    place(deps.as_mut(), &order("m1", "carol", 1, false, price(2), 10));
+    place(deps.as_mut(), &order("b1", "bob", 0, true, price(1), 10));
+
+    // only c1 is a sell of the same pair at a price the taker crosses
+    let taker = order("d1", "dave", 0, true, price(3), 30);
+    let res = take(deps.as_mut(), &taker, &["m1", "c2", "b1", "x", "c1"], None);
+    let outcomes = outcomes(&res);
+    let statuses: Vec<MatchStatus> = outcomes.iter().map(|outcome| outcome.status).collect();
+    assert_eq!(
+        statuses,
+        vec![
+            MatchStatus::Skipped,
+            MatchStatus::Skipped,
+            MatchStatus::Skipped,
+            MatchStatus::Skipped,
+            MatchStatus::Filled,
+        ]
+    );
+    assert!(outcomes[..4].iter().all(|outcome| outcome.reason.is_some()));
+    assert_eq!(outcomes[4].price, Some(price(2)));
+    assert_eq!(outcomes[4].quantity.u128(), 10);
+
+    // the fill settles at the maker's price, not the taker's
+    assert_eq!(balance(deps.as_ref(), "dave", "uusd"), (80, 0));
+    assert_eq!(balance(deps.as_ref(), "dave", "uluna"), (10, 0));
+    assert_eq!(balance(deps.as_ref(), "carol", "uusd"), (20, 0));
+    assert_eq!(
+        resting(deps.as_ref(), false),
+        vec![("c2".to_string(), 10), ("m1".to_string(), 10)]
+    );
+}
+
+#[test]
+fn orders_are_taken_and_placed_in_the_senders_name_only() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "carol", "uluna", 10);
+    deposit(deps.as_mut(), "dave", "uusd", 100);
+    place(deps.as_mut(), &order("c1", "carol", 0, false, price(2), 10));
+
+    let msg = |taker: TraderRecord, add_order: Option<TraderRecord>| ExecuteMsg::Order {
+        order: taker,
+        add_order,
+        update_order: Some(CounterOrder {
+            order_id: "c1".to_string(),
+            quantity: Uint128::new(5),
+        }),
+        remove_orders: None,
+        self_trade_prevention: None,
+    };
+    let as_carol = order("d1", "carol", 0, true, price(2), 5);
+    let err = run(deps.as_mut(), "dave", msg(as_carol.clone(), None)).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    let own = order("d1", "dave", 0, true, price(2), 5);
+    let err = run(deps.as_mut(), "dave", msg(own.clone(), Some(as_carol))).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    // the fill quantity is capped by the taker's own size
+    let small = order("d1", "dave", 0, true, price(2), 4);
+    let err = run(deps.as_mut(), "dave", msg(small, None)).unwrap_err();
+    assert_eq!(err, ContractError::InvalidInput {});
+
+    run(deps.as_mut(), "dave", msg(own, None)).unwrap();
+    assert_eq!(resting(deps.as_ref(), false), vec![("c1".to_string(), 5)]);
+    assert_eq!(balance(deps.as_ref(), "dave", "uusd"), (90, 0));
+}
+
+#[test]
+fn unfunded_makers_are_evicted() {
+    let mut deps = mock_dependencies();
+    setup(deps.as_mut(), token_pairs());
+    deps.querier.with_token_balances(&[
+        ("usdt", &[("alice", 1000)]),
+        ("lunt", &[("bob", 50), ("carol", 50)]),
+    ]);
+    place(deps.as_mut(), &order("b1", "bob", 0, false, price(2), 50));
+    place(deps.as_mut(), &order("c1", "carol", 0, false, price(2), 50));
+    // bob's tokens leave after the order rested
+    deps.querier.with_token_balances(&[("lunt", &[("bob", 0)])]);
+
+    let res = take(
+        deps.as_mut(),
+        &order("a1", "alice", 0, true, price(2), 100),
+        &["b1", "c1"],
+        None,
+    );
+    let outcomes = outcomes(&res);
+    assert_eq!(outcomes[0].status, MatchStatus::Evicted);
+    assert_eq!(outcomes[1].status, MatchStatus::Filled);
+    assert!(resting(deps.as_ref(), false).is_empty());
+    assert_eq!(res.messages.len(), 3);
+}
+
+#[test]
+fn maker_holding_exactly_its_cost_is_filled() {
+    let mut deps = mock_dependencies();
+    setup(deps.as_mut(), token_pairs());
+    // 1.5 at a price of 1.5 costs 2.25 of bob's 2.25
+    deps.querier.with_token_balances(&[
+        ("usdt", &[("bob", 2_250_000)]),
+        ("lunt", &[("carol", 1_500_000)]),
+    ]);
+    let bid = Uint128::new(1_500_000);
+    place(deps.as_mut(), &order("b1", "bob", 0, true, bid, 1_500_000));
+
+    let res = fill(
+        deps.as_mut(),
+        &order("c1", "carol", 0, false, bid, 1_200_000),
+        "b1",
+    );
+    assert_eq!(outcomes(&res)[0].status, MatchStatus::Filled);
+    assert_eq!(
+        resting(deps.as_ref(), true),
+        vec![("b1".to_string(), 300_000)]
+    );
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use cosmwasm_std::testing::{MockApi, MockQuerier, MockStorage};
+use cosmwasm_std::{
+    from_binary, from_slice, to_binary, Coin, ContractResult, Empty, OwnedDeps, Querier,
+    QuerierResult, QueryRequest, SystemError, SystemResult, Uint128, WasmQuery,
+};
+use cw20::{AllowanceResponse, BalanceResponse, Cw20QueryMsg, Expiration};
+
+// Answers CW20 balance and allowance queries from tables, unless set otherwise the allowance
+// of an owner is its whole balance. Everything else goes to the default MockQuerier.
+pub struct WasmMockQuerier {
+    base: MockQuerier<Empty>,
+    // token contract => (owner => balance)
+    token_balances: HashMap<String, HashMap<String, Uint128>>,
+    // (token contract, owner) => allowance
+    allowances: HashMap<(String, String), AllowanceResponse>,
+}
+
+impl Querier for WasmMockQuerier {
+    fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+        let request: QueryRequest<Empty> = match from_slice(bin_request) {
+            Ok(v) => v,
+            Err(e) => {
+                return SystemResult::Err(SystemError::InvalidRequest {
+                    error: format!("Parsing query request: {}", e),
+                    request: bin_request.into(),
+                })
+            }
+        };
+        self.handle_query(&request)
+    }
+}
+
+impl WasmMockQuerier {
+    pub fn handle_query(&self, request: &QueryRequest<Empty>) -> QuerierResult {
+        match request {
+            QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg }) => {
+                let balance_of = |address: &str| {
+                    self.token_balances
+                        .get(contract_addr)
+                        .and_then(|balances| balances.get(address))
+                        .cloned()
+                        .unwrap_or_default()
+                };
+                let response = match from_binary(msg).unwrap() {
+                    Cw20QueryMsg::Balance { address } => to_binary(&BalanceResponse {
+                        balance: balance_of(&address),
+                    }),
+                    Cw20QueryMsg::Allowance { owner, .. } => {
+                        let key = (contract_addr.clone(), owner.clone());
+                        to_binary(&self.allowances.get(&key).cloned().unwrap_or(
+                            AllowanceResponse {
+                                allowance: balance_of(&owner),
+                                expires: Expiration::Never {},
+                            },
+                        ))
+                    }
+                    _ => panic!("DO NOT ENTER HERE"),
+                };
+                SystemResult::Ok(ContractResult::from(response))
+            }
+            _ => self.base.handle_query(request),
+        }
+    }
+
+    pub fn with_balance(&mut self, address: &str, balance: Vec<Coin>) {
+        self.base.update_balance(address, balance);
+    }
+
+    pub fn with_token_balances(&mut self, balances: &[(&str, &[(&str, u128)])]) {
+        for (contract_addr, owners) in balances.iter() {
+            let table = self
+                .token_balances
+                .entry(contract_addr.to_string())
+                .or_default();
+            for (owner, balance) in owners.iter() {
+                table.insert(owner.to_string(), Uint128::new(*balance));
+            }
+        }
+    }
+
+    pub fn with_allowance(
+        &mut self,
+        contract_addr: &str,
+        owner: &str,
+        allowance: u128,
+        expires: Expiration,
+    ) {
+        self.allowances.insert(
+            (contract_addr.to_string(), owner.to_string()),
+            AllowanceResponse {
+                allowance: Uint128::new(allowance),
+                expires,
+            },
+        );
+    }
+}
+
+pub fn mock_dependencies() -> OwnedDeps<MockStorage, MockApi, WasmMockQuerier> {
+    OwnedDeps {
+        storage: MockStorage::default(),
+        api: MockApi::default(),
+        querier: WasmMockQuerier {
+            base: MockQuerier::new(&[]),
+            token_balances: HashMap::new(),
+            allowances: HashMap::new(),
+        },
+    }
+}
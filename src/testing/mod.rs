@@ -0,0 +1,248 @@
+use cosmwasm_std::testing::{mock_env, mock_info, MockApi, MockStorage};
+use cosmwasm_std::{coins, from_binary, Addr, Deps, DepsMut, OwnedDeps, Response, Uint128};
+
+use crate::contract::{execute, instantiate, query, NORMAL_DECIMAL};
+use crate::error::ContractError;
+use crate::msg::{
+    BalancesResponse, CounterOrder, ExecuteMsg, PairStatus, QueryMsg, SelfTradePrevention,
+    TriggerListResponse,
+};
+
+use classic_terrapexc::asset::AssetInfo;
+use classic_terrapexc::trading::{InstantiateMsg, PairInfo, TraderListResponse, TraderRecord};
+
+mod allowance;
+mod amend;
+mod cancel;
+mod events;
+mod factory;
+mod groups;
+mod hooks;
+mod iceberg;
+mod ledger;
+mod matching;
+mod mock_querier;
+mod pair;
+mod route;
+mod settlement;
+mod simulate;
+mod stp;
+mod trailing;
+mod triggers;
+
+use mock_querier::WasmMockQuerier;
+
+const OWNER: &str = "owner";
+
+fn native(denom: &str) -> AssetInfo {
+    AssetInfo::NativeToken {
+        denom: denom.to_string(),
+    }
+}
+
+// uluna priced in uusd, and umnt priced in uusd
+fn token(contract_addr: &str) -> AssetInfo {
+    AssetInfo::Token {
+        contract_addr: contract_addr.to_string(),
+    }
+}
+
+// pair 0 trades lunt for usdt, both CW20s
+fn token_pairs() -> Vec<PairInfo> {
+    vec![PairInfo {
+        from_asset: token("usdt"),
+        to_asset: token("lunt"),
+    }]
+}
+
+fn native_pairs() -> Vec<PairInfo> {
+    vec![
+        PairInfo {
+            from_asset: native("uusd"),
+            to_asset: native("uluna"),
+        },
+        PairInfo {
+            from_asset: native("uusd"),
+            to_asset: native("umnt"),
+        },
+    ]
+}
+
+fn setup(deps: DepsMut, pair_list: Vec<PairInfo>) {
+    let msg = InstantiateMsg {
+        pair_list,
+        enabled: true,
+    };
+    instantiate(deps, mock_env(), mock_info(OWNER, &[]), msg).unwrap();
+}
+
+fn setup_ledger(deps: &mut OwnedDeps<MockStorage, MockApi, WasmMockQuerier>) {
+    setup(deps.as_mut(), native_pairs());
+    run(
+        deps.as_mut(),
+        OWNER,
+        ExecuteMsg::UpdateConfig {
+            owner: None,
+            pair_list: None,
+            enabled: None,
+            ledger: Some(true),
+            treasury: None,
+            pair_code_id: None,
+        },
+    )
+    .unwrap();
+}
+
+fn set_status(deps: DepsMut, pair_id: u128, status: PairStatus) {
+    let msg = ExecuteMsg::UpdatePair {
+        pair_id: Uint128::new(pair_id),
+        status: Some(status),
+        self_trade_prevention: None,
+        price_band: None,
+        circuit_breaker: None,
+        token_transfer_mode: None,
+    };
+    run(deps, OWNER, msg).unwrap();
+}
+
+fn price(units: u128) -> Uint128 {
+    Uint128::new(units * NORMAL_DECIMAL)
+}
+
+fn order(
+    id: &str,
+    address: &str,
+    pair_id: u128,
+    is_buy: bool,
+    price: Uint128,
+    amount: u128,
+) -> TraderRecord {
+    TraderRecord {
+        id: id.to_string(),
+        address: Addr::unchecked(address),
+        pair_id: Uint128::new(pair_id),
+        is_buy,
+        price,
+        order_stock_amount: Uint128::new(amount),
+        current_stock_amount: Uint128::new(amount),
+    }
+}
+
+fn run(deps: DepsMut, sender: &str, msg: ExecuteMsg) -> Result<Response, ContractError> {
+    execute(deps, mock_env(), mock_info(sender, &[]), msg)
+}
+
+fn deposit(deps: DepsMut, sender: &str, denom: &str, amount: u128) {
+    let info = mock_info(sender, &coins(amount, denom));
+    execute(deps, mock_env(), info, ExecuteMsg::Deposit {}).unwrap();
+}
+
+// Rests the whole order in the book.
+fn place(deps: DepsMut, record: &TraderRecord) -> Response {
+    let msg = ExecuteMsg::Order {
+        order: record.clone(),
+        add_order: Some(record.clone()),
+        update_order: None,
+        remove_orders: None,
+        self_trade_prevention: None,
+    };
+    run(deps, record.address.as_str(), msg).unwrap()
+}
+
+// Fills `record.current_stock_amount` of the resting order `order_id`, which keeps the rest.
+fn fill(deps: DepsMut, record: &TraderRecord, order_id: &str) -> Response {
+    let msg = ExecuteMsg::Order {
+        order: record.clone(),
+        add_order: None,
+        update_order: Some(CounterOrder {
+            order_id: order_id.to_string(),
+            quantity: record.current_stock_amount,
+        }),
+        remove_orders: None,
+        self_trade_prevention: None,
+    };
+    run(deps, record.address.as_str(), msg).unwrap()
+}
+
+// Takes the listed resting orders whole.
+fn take(
+    deps: DepsMut,
+    record: &TraderRecord,
+    remove_orders: &[&str],
+    self_trade_prevention: Option<SelfTradePrevention>,
+) -> Response {
+    let msg = ExecuteMsg::Order {
+        order: record.clone(),
+        add_order: None,
+        update_order: None,
+        remove_orders: Some(remove_orders.iter().map(|id| id.to_string()).collect()),
+        self_trade_prevention,
+    };
+    run(deps, record.address.as_str(), msg).unwrap()
+}
+
+// (free, locked) ledger balance of `address` in `asset`
+fn balance(deps: Deps, address: &str, asset: &str) -> (u128, u128) {
+    let res: BalancesResponse = from_binary(
+        &query(
+            deps,
+            mock_env(),
+            QueryMsg::Balances {
+                address: address.to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    res.balances
+        .iter()
+        .find(|balance| balance.asset == asset)
+        .map(|balance| (balance.free.u128(), balance.locked.u128()))
+        .unwrap_or_default()
+}
+
+fn resting(deps: Deps, is_buy: bool) -> Vec<(String, u128)> {
+    let res: TraderListResponse = from_binary(
+        &query(
+            deps,
+            mock_env(),
+            QueryMsg::ListOrders {
+                is_buy,
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    res.traders
+        .into_iter()
+        .map(|trader| (trader.id, trader.current_stock_amount.u128()))
+        .collect()
+}
+
+fn triggers(deps: Deps, pair_id: u128) -> Vec<(String, u128)> {
+    let res: TriggerListResponse = from_binary(
+        &query(
+            deps,
+            mock_env(),
+            QueryMsg::ListTriggers {
+                pair_id: Uint128::new(pair_id),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    res.triggers
+        .into_iter()
+        .map(|trigger| (trigger.id, trigger.current_stock_amount.u128()))
+        .collect()
+}
+
+fn has_attribute(res: &Response, key: &str, value: &str) -> bool {
+    res.attributes
+        .iter()
+        .any(|attr| attr.key == key && attr.value == value)
+}
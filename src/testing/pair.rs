@@ -0,0 +1,239 @@
+use cosmwasm_std::testing::{mock_env, mock_info};
+use cosmwasm_std::{Decimal, DepsMut, Uint128};
+
+use crate::contract::execute;
+use crate::error::ContractError;
+use crate::msg::{CircuitBreaker, CounterOrder, ExecuteMsg, PairStatus, PriceBand, ReferencePrice};
+use crate::testing::mock_querier::mock_dependencies;
+use crate::testing::{
+    balance, deposit, fill, native, order, place, price, resting, run, set_status, setup_ledger,
+    OWNER,
+};
+
+fn cancel(order_id: &str, is_buy: bool) -> ExecuteMsg {
+    ExecuteMsg::Cancel {
+        order_id: order_id.to_string(),
+        is_buy,
+    }
+}
+
+fn place_msg(id: &str) -> ExecuteMsg {
+    let record = order(id, "alice", 0, false, price(2), 10);
+    ExecuteMsg::Order {
+        order: record.clone(),
+        add_order: Some(record),
+        update_order: None,
+        remove_orders: None,
+        self_trade_prevention: None,
+    }
+}
+
+#[test]
+fn disabled_contract_only_lets_funds_out() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "alice", "uluna", 30);
+    place(deps.as_mut(), &order("a1", "alice", 0, false, price(2), 10));
+    run(
+        deps.as_mut(),
+        OWNER,
+        ExecuteMsg::UpdateConfig {
+            owner: None,
+            pair_list: None,
+            enabled: Some(false),
+            ledger: None,
+            treasury: None,
+            pair_code_id: None,
+        },
+    )
+    .unwrap();
+
+    let err = run(deps.as_mut(), "alice", place_msg("a2")).unwrap_err();
+    assert_eq!(err, ContractError::Disabled {});
+    run(deps.as_mut(), "alice", cancel("a1", false)).unwrap();
+    run(
+        deps.as_mut(),
+        "alice",
+        ExecuteMsg::Withdraw {
+            asset: native("uluna"),
+            amount: Uint128::new(30),
+        },
+    )
+    .unwrap();
+    assert!(resting(deps.as_ref(), false).is_empty());
+    assert_eq!(balance(deps.as_ref(), "alice", "uluna"), (0, 0));
+}
+
+#[test]
+fn pair_status_gates_placing_matching_and_cancelling() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "alice", "uluna", 30);
+    deposit(deps.as_mut(), "bob", "uusd", 100);
+    place(deps.as_mut(), &order("a1", "alice", 0, false, price(2), 10));
+
+    let err = run(
+        deps.as_mut(),
+        "alice",
+        ExecuteMsg::UpdatePair {
+            pair_id: Uint128::zero(),
+            status: Some(PairStatus::Halted),
+            self_trade_prevention: None,
+            price_band: None,
+            circuit_breaker: None,
+            token_transfer_mode: None,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    // post only rests orders but does not match them
+    set_status(deps.as_mut(), 0, PairStatus::PostOnly);
+    run(deps.as_mut(), "alice", place_msg("a2")).unwrap();
+    let take = ExecuteMsg::Order {
+        order: order("b1", "bob", 0, true, price(2), 5),
+        add_order: None,
+        update_order: Some(CounterOrder {
+            order_id: "a1".to_string(),
+            quantity: Uint128::new(5),
+        }),
+        remove_orders: None,
+        self_trade_prevention: None,
+    };
+    let err = run(deps.as_mut(), "bob", take).unwrap_err();
+    assert_eq!(err, ContractError::PairUnavailable {});
+
+    // cancel only lets orders leave
+    set_status(deps.as_mut(), 0, PairStatus::CancelOnly);
+    let err = run(deps.as_mut(), "alice", place_msg("a3")).unwrap_err();
+    assert_eq!(err, ContractError::PairUnavailable {});
+    run(deps.as_mut(), "alice", cancel("a2", false)).unwrap();
+
+    // halted freezes the book
+    set_status(deps.as_mut(), 0, PairStatus::Halted);
+    let err = run(deps.as_mut(), "alice", cancel("a1", false)).unwrap_err();
+    assert_eq!(err, ContractError::PairUnavailable {});
+
+    set_status(deps.as_mut(), 0, PairStatus::Active);
+    fill(
+        deps.as_mut(),
+        &order("b1", "bob", 0, true, price(2), 5),
+        "a1",
+    );
+    assert_eq!(resting(deps.as_ref(), false), vec![("a1".to_string(), 5)]);
+}
+
+fn update_pair(
+    deps: DepsMut,
+    price_band: Option<PriceBand>,
+    circuit_breaker: Option<CircuitBreaker>,
+) {
+    let msg = ExecuteMsg::UpdatePair {
+        pair_id: Uint128::zero(),
+        status: None,
+        self_trade_prevention: None,
+        price_band,
+        circuit_breaker,
+        token_transfer_mode: None,
+    };
+    run(deps, OWNER, msg).unwrap();
+}
+
+#[test]
+fn price_band_follows_the_last_trade() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "alice", "uluna", 100);
+    deposit(deps.as_mut(), "bob", "uusd", 100);
+    update_pair(
+        deps.as_mut(),
+        Some(PriceBand {
+            max_deviation: Decimal::percent(10),
+            reference: ReferencePrice::LastTrade,
+        }),
+        None,
+    );
+
+    // without a trade there is no reference to deviate from
+    place(
+        deps.as_mut(),
+        &order("a1", "alice", 0, false, price(20), 10),
+    );
+    place(deps.as_mut(), &order("b1", "bob", 0, true, price(2), 10));
+    fill(
+        deps.as_mut(),
+        &order("a2", "alice", 0, false, price(2), 5),
+        "b1",
+    );
+
+    let outside = order("a3", "alice", 0, false, Uint128::new(2_300_000), 10);
+    let err = run(
+        deps.as_mut(),
+        "alice",
+        ExecuteMsg::Order {
+            order: outside.clone(),
+            add_order: Some(outside),
+            update_order: None,
+            remove_orders: None,
+            self_trade_prevention: None,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::PriceOutOfBand {});
+    place(
+        deps.as_mut(),
+        &order("a4", "alice", 0, false, Uint128::new(2_200_000), 10),
+    );
+}
+
+#[test]
+fn circuit_breaker_halts_the_pair_for_the_cooldown() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "bob", "uusd", 100);
+    deposit(deps.as_mut(), "carol", "uluna", 100);
+    deposit(deps.as_mut(), "dave", "uluna", 10);
+    deposit(deps.as_mut(), "dave", "uusd", 100);
+    update_pair(
+        deps.as_mut(),
+        None,
+        Some(CircuitBreaker {
+            threshold: Decimal::percent(10),
+            window: 100,
+            cooldown: 60,
+        }),
+    );
+    place(deps.as_mut(), &order("b1", "bob", 0, true, price(2), 10));
+    place(deps.as_mut(), &order("c1", "carol", 0, false, price(3), 10));
+
+    // the first fill opens the window, a move of 50% within it trips the breaker
+    fill(
+        deps.as_mut(),
+        &order("d1", "dave", 0, false, price(2), 5),
+        "b1",
+    );
+    fill(
+        deps.as_mut(),
+        &order("d2", "dave", 0, true, price(3), 5),
+        "c1",
+    );
+
+    let until = mock_env().block.time.seconds() + 60;
+    let again = order("c2", "carol", 0, false, price(3), 10);
+    let place_again = ExecuteMsg::Order {
+        order: again.clone(),
+        add_order: Some(again),
+        update_order: None,
+        remove_orders: None,
+        self_trade_prevention: None,
+    };
+    let err = run(deps.as_mut(), "carol", place_again.clone()).unwrap_err();
+    assert_eq!(err, ContractError::CircuitBreakerHalted { until });
+    // cancels still go through while it cools down
+    run(deps.as_mut(), "carol", cancel("c1", false)).unwrap();
+
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(60);
+    execute(deps.as_mut(), env, mock_info("carol", &[]), place_again).unwrap();
+    assert_eq!(resting(deps.as_ref(), false), vec![("c2".to_string(), 10)]);
+}
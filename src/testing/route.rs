@@ -0,0 +1,202 @@
+use cosmwasm_std::testing::{mock_env, MockApi, MockStorage};
+use cosmwasm_std::{from_binary, Deps, Order, OwnedDeps, StdResult, Uint128};
+
+use crate::contract::{migrate, query, unindex_order};
+use crate::error::ContractError;
+use crate::msg::{
+    ExecuteMsg, OrderType, QueryMsg, Side, SimulateOrderResponse, SimulateRouteResponse,
+    TriggerType,
+};
+use crate::state::{
+    pair_key, side_key, IndexMigration, BUYERS, INDEX_MIGRATION, ORDERS_OF, ORDER_INDEX,
+    ORDER_PRIORITY, SELLERS,
+};
+use crate::testing::mock_querier::{mock_dependencies, WasmMockQuerier};
+use crate::testing::{
+    balance, deposit, has_attribute, native, order, place, price, run, setup_ledger, triggers,
+};
+use classic_terrapexc::trading::{MigrateMsg, TraderRecord};
+
+// bob buys uluna for uusd on pair 0, carol sells umnt for uusd on pair 1
+fn route_books() -> OwnedDeps<MockStorage, MockApi, WasmMockQuerier> {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "bob", "uusd", 200);
+    deposit(deps.as_mut(), "carol", "umnt", 100);
+    deposit(deps.as_mut(), "alice", "uluna", 50);
+    place(deps.as_mut(), &order("b1", "bob", 0, true, price(2), 100));
+    place(
+        deps.as_mut(),
+        &order("c1", "carol", 1, false, price(1), 100),
+    );
+    deps
+}
+
+fn route(min_receive: u128) -> ExecuteMsg {
+    ExecuteMsg::Route {
+        offer_asset: native("uluna"),
+        ask_asset: native("umnt"),
+        amount: Uint128::new(50),
+        min_receive: Uint128::new(min_receive),
+        path: vec![Uint128::zero(), Uint128::new(1)],
+    }
+}
+
+#[test]
+fn route_through_two_pairs() {
+    let mut deps = route_books();
+
+    // uluna is sold for uusd on pair 0, which buys umnt on pair 1
+    let simulated: SimulateRouteResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::SimulateRoute {
+                offer_asset: native("uluna"),
+                ask_asset: native("umnt"),
+                amount: Uint128::new(50),
+                path: vec![Uint128::zero(), Uint128::new(1)],
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(simulated.receive_amount, Uint128::new(100));
+    assert_eq!(simulated.hops[0].receive_amount, Uint128::new(100));
+
+    let res = run(deps.as_mut(), "alice", route(100)).unwrap();
+    assert!(has_attribute(&res, "receive", "100"));
+    assert_eq!(balance(deps.as_ref(), "alice", "uluna"), (0, 0));
+    assert_eq!(balance(deps.as_ref(), "alice", "uusd"), (0, 0));
+    assert_eq!(balance(deps.as_ref(), "alice", "umnt"), (100, 0));
+    assert_eq!(balance(deps.as_ref(), "bob", "uluna"), (50, 0));
+    assert_eq!(balance(deps.as_ref(), "bob", "uusd"), (0, 100));
+    assert_eq!(balance(deps.as_ref(), "carol", "uusd"), (100, 0));
+
+    // what the sender offers comes out of its free ledger balance
+    let err = run(deps.as_mut(), "alice", route(0)).unwrap_err();
+    assert_eq!(err, ContractError::InsufficientFunds {});
+}
+
+#[test]
+fn route_below_min_receive_fails() {
+    let mut deps = route_books();
+    let err = run(deps.as_mut(), "alice", route(101)).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::MinReceiveNotReached {
+            receive: Uint128::new(100)
+        }
+    );
+}
+
+// ids of the resting sells of pair 0 in the order a market buy would take them
+fn sell_queue(deps: Deps) -> Vec<String> {
+    let res: SimulateOrderResponse = from_binary(
+        &query(
+            deps,
+            mock_env(),
+            QueryMsg::SimulateOrder {
+                pair_id: Uint128::zero(),
+                side: Side::Buy,
+                quantity: Uint128::new(1000),
+                price: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    res.fills.into_iter().map(|fill| fill.order_id).collect()
+}
+
+// ids of the sells of pair 0 in the price index
+fn indexed_sells(deps: Deps) -> Vec<String> {
+    ORDER_INDEX
+        .prefix((pair_key(Uint128::zero()), side_key(false)))
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, order_id)| order_id))
+        .collect::<StdResult<Vec<String>>>()
+        .unwrap()
+}
+
+#[test]
+fn migration_indexes_the_book_page_by_page() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "bob", "uusd", 200);
+    deposit(deps.as_mut(), "carol", "uluna", 200);
+    place(deps.as_mut(), &order("b1", "bob", 0, true, price(1), 100));
+    place(deps.as_mut(), &order("c1", "carol", 0, false, price(3), 50));
+    place(deps.as_mut(), &order("c2", "carol", 0, false, price(2), 50));
+    run(
+        deps.as_mut(),
+        "carol",
+        ExecuteMsg::PlaceTrigger {
+            order: order("s1", "carol", 0, false, Uint128::zero(), 50),
+            trigger_type: TriggerType::StopLoss,
+            trigger_price: price(1),
+            order_type: OrderType::Market,
+        },
+    )
+    .unwrap();
+
+    // the book as it was before orders were indexed
+    for book in [&BUYERS, &SELLERS] {
+        let records = book
+            .range(&deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, record)| record))
+            .collect::<StdResult<Vec<TraderRecord>>>()
+            .unwrap();
+        for record in records {
+            unindex_order(deps.as_mut().storage, &record).unwrap();
+            let side = side_key(record.is_buy);
+            ORDER_PRIORITY.remove(deps.as_mut().storage, (side.clone(), record.id.clone()));
+            ORDERS_OF.remove(deps.as_mut().storage, (record.address, side, record.id));
+        }
+    }
+    assert!(indexed_sells(deps.as_ref()).is_empty());
+    INDEX_MIGRATION
+        .save(
+            deps.as_mut().storage,
+            &IndexMigration {
+                stage: 0,
+                start_after: None,
+            },
+        )
+        .unwrap();
+
+    let migrate_index = ExecuteMsg::MigrateIndex { limit: Some(2) };
+    let res = run(deps.as_mut(), "anyone", migrate_index.clone()).unwrap();
+    assert!(has_attribute(&res, "done", "false"));
+    assert_eq!(indexed_sells(deps.as_ref()), vec!["c1"]);
+    // nothing trades on a half indexed book
+    let err = run(
+        deps.as_mut(),
+        "carol",
+        ExecuteMsg::Order {
+            order: order("c3", "carol", 0, false, price(2), 10),
+            add_order: Some(order("c3", "carol", 0, false, price(2), 10)),
+            update_order: None,
+            remove_orders: None,
+            self_trade_prevention: None,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::PairUnavailable {});
+
+    let res = run(deps.as_mut(), "anyone", migrate_index.clone()).unwrap();
+    assert!(has_attribute(&res, "done", "false"));
+    assert_eq!(indexed_sells(deps.as_ref()), vec!["c2", "c1"]);
+    let res = run(deps.as_mut(), "anyone", migrate_index.clone()).unwrap();
+    assert!(has_attribute(&res, "done", "true"));
+    assert_eq!(sell_queue(deps.as_ref()), vec!["c2", "c1"]);
+    assert_eq!(triggers(deps.as_ref(), 0), vec![("s1".to_string(), 50)]);
+    let err = run(deps.as_mut(), "anyone", migrate_index).unwrap_err();
+    assert_eq!(err, ContractError::InvalidInput {});
+
+    // a book within one page is indexed by the migration itself
+    let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+    assert!(has_attribute(&res, "done", "true"));
+    assert_eq!(sell_queue(deps.as_ref()), vec!["c2", "c1"]);
+    place(deps.as_mut(), &order("c3", "carol", 0, false, price(2), 10));
+}
@@ -0,0 +1,175 @@
+use cosmwasm_std::testing::{mock_env, MockApi, MockStorage};
+use cosmwasm_std::{
+    from_binary, to_binary, ContractResult, CosmosMsg, OwnedDeps, Reply, ReplyOn, Response, SubMsg,
+    Uint128, WasmMsg,
+};
+use cw20::Cw20ExecuteMsg;
+
+use crate::claim::PAYOUT_REPLY_ID;
+use crate::contract::{query, reply};
+use crate::error::ContractError;
+use crate::msg::{ClaimsResponse, CounterOrder, ExecuteMsg, OrderType, QueryMsg, TriggerType};
+use crate::testing::mock_querier::{mock_dependencies, WasmMockQuerier};
+use crate::testing::{native_pairs, order, place, price, run, setup, take, token, token_pairs};
+
+fn cw20_message(contract_addr: &str, msg: Cw20ExecuteMsg) -> CosmosMsg {
+    CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: contract_addr.to_string(),
+        msg: to_binary(&msg).unwrap(),
+        funds: vec![],
+    })
+}
+
+// alice buys 100 from two orders of bob at 2
+fn two_fills(deps: &mut OwnedDeps<MockStorage, MockApi, WasmMockQuerier>) -> Response {
+    setup(deps.as_mut(), token_pairs());
+    deps.querier
+        .with_token_balances(&[("usdt", &[("alice", 1000)]), ("lunt", &[("bob", 1000)])]);
+    place(deps.as_mut(), &order("b1", "bob", 0, false, price(2), 50));
+    place(deps.as_mut(), &order("b2", "bob", 0, false, price(2), 50));
+    take(
+        deps.as_mut(),
+        &order("a1", "alice", 0, true, price(2), 100),
+        &["b1", "b2"],
+        None,
+    )
+}
+
+#[test]
+fn fills_are_netted_per_token_and_direction() {
+    let mut deps = mock_dependencies();
+    let res = two_fills(&mut deps);
+
+    // one transfer per (token, from, to), the maker is paid through the contract
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(cw20_message(
+                "lunt",
+                Cw20ExecuteMsg::TransferFrom {
+                    owner: "bob".to_string(),
+                    recipient: "alice".to_string(),
+                    amount: Uint128::new(100),
+                },
+            )),
+            SubMsg::new(cw20_message(
+                "usdt",
+                Cw20ExecuteMsg::TransferFrom {
+                    owner: "alice".to_string(),
+                    recipient: mock_env().contract.address.to_string(),
+                    amount: Uint128::new(200),
+                },
+            )),
+            SubMsg {
+                id: PAYOUT_REPLY_ID,
+                msg: cw20_message(
+                    "usdt",
+                    Cw20ExecuteMsg::Transfer {
+                        recipient: "bob".to_string(),
+                        amount: Uint128::new(200),
+                    },
+                ),
+                gas_limit: None,
+                reply_on: ReplyOn::Error,
+            },
+        ]
+    );
+}
+
+#[test]
+fn native_pairs_only_trade_on_the_ledger() {
+    let mut deps = mock_dependencies();
+    setup(deps.as_mut(), native_pairs());
+
+    let record = order("a1", "alice", 0, false, price(2), 10);
+    let err = run(
+        deps.as_mut(),
+        "alice",
+        ExecuteMsg::Order {
+            order: record.clone(),
+            add_order: Some(record.clone()),
+            update_order: None,
+            remove_orders: None,
+            self_trade_prevention: None,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::UnacceptableToken {});
+
+    let err = run(
+        deps.as_mut(),
+        "alice",
+        ExecuteMsg::Order {
+            order: order("a1", "alice", 0, false, price(2), 10),
+            add_order: None,
+            update_order: Some(CounterOrder {
+                order_id: "b1".to_string(),
+                quantity: Uint128::new(5),
+            }),
+            remove_orders: None,
+            self_trade_prevention: None,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::UnacceptableToken {});
+
+    let err = run(
+        deps.as_mut(),
+        "alice",
+        ExecuteMsg::PlaceTrigger {
+            order: order("s1", "alice", 0, false, Uint128::zero(), 10),
+            trigger_type: TriggerType::StopLoss,
+            trigger_price: price(1),
+            order_type: OrderType::Market,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::UnacceptableToken {});
+}
+
+#[test]
+fn failed_payouts_are_claimable() {
+    let mut deps = mock_dependencies();
+    two_fills(&mut deps);
+
+    // a payout that fails stays with the contract for the maker to claim
+    reply(
+        deps.as_mut(),
+        mock_env(),
+        Reply {
+            id: PAYOUT_REPLY_ID,
+            result: ContractResult::Err("blocked recipient".to_string()),
+        },
+    )
+    .unwrap();
+    let claims: ClaimsResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Claims {
+                address: "bob".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(claims.claims[0].asset, "usdt");
+    assert_eq!(claims.claims[0].amount, Uint128::new(200));
+
+    let claim = ExecuteMsg::Claim {
+        asset: token("usdt"),
+    };
+    let res = run(deps.as_mut(), "bob", claim.clone()).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(cw20_message(
+            "usdt",
+            Cw20ExecuteMsg::Transfer {
+                recipient: "bob".to_string(),
+                amount: Uint128::new(200),
+            },
+        ))]
+    );
+    let err = run(deps.as_mut(), "bob", claim).unwrap_err();
+    assert_eq!(err, ContractError::InvalidZeroAmount {});
+}
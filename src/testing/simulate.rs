@@ -0,0 +1,122 @@
+use cosmwasm_std::testing::mock_env;
+use cosmwasm_std::{from_binary, Deps, StdResult, Uint128};
+
+use crate::contract::query;
+use crate::msg::{PairStatus, QueryMsg, ReverseSimulateResponse, Side, SimulateOrderResponse};
+use crate::testing::mock_querier::mock_dependencies;
+use crate::testing::{deposit, order, place, price, set_status, setup_ledger};
+
+fn simulate(deps: Deps, quantity: u128, price: Option<Uint128>) -> SimulateOrderResponse {
+    from_binary(
+        &query(
+            deps,
+            mock_env(),
+            QueryMsg::SimulateOrder {
+                pair_id: Uint128::zero(),
+                side: Side::Buy,
+                quantity: Uint128::new(quantity),
+                price,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap()
+}
+
+#[test]
+fn simulations_walk_the_book() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "bob", "uluna", 200);
+    place(deps.as_mut(), &order("b1", "bob", 0, false, price(3), 100));
+    place(deps.as_mut(), &order("b2", "bob", 0, false, price(2), 100));
+
+    // best price first
+    let res = simulate(deps.as_ref(), 150, None);
+    assert_eq!(res.fills[0].order_id, "b2");
+    assert_eq!(res.fills[1].quantity, Uint128::new(50));
+    assert_eq!(res.filled_quantity, Uint128::new(150));
+    assert_eq!(res.cost, Uint128::new(350));
+    assert_eq!(res.average_price, Some(Uint128::new(2_333_333)));
+    assert!(!res.rests);
+
+    // a limit stops at its price and rests the rest
+    let res = simulate(deps.as_ref(), 150, Some(price(2)));
+    assert_eq!(res.filled_quantity, Uint128::new(100));
+    assert_eq!(res.remaining_quantity, Uint128::new(50));
+    assert!(res.rests);
+
+    // a market order rests nothing, whatever the book lacks
+    let res = simulate(deps.as_ref(), 300, None);
+    assert_eq!(res.filled_quantity, Uint128::new(200));
+    assert_eq!(res.remaining_quantity, Uint128::new(100));
+    assert!(!res.rests);
+}
+
+#[test]
+fn post_only_pairs_simulate_resting_orders() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "bob", "uluna", 100);
+    place(deps.as_mut(), &order("b1", "bob", 0, false, price(2), 100));
+    set_status(deps.as_mut(), 0, PairStatus::PostOnly);
+
+    let res = simulate(deps.as_ref(), 50, Some(price(2)));
+    assert!(res.fills.is_empty());
+    assert_eq!(res.remaining_quantity, Uint128::new(50));
+    assert!(res.rests);
+}
+
+fn reverse_simulate(
+    deps: Deps,
+    side: Side,
+    desired_receive: u128,
+) -> StdResult<ReverseSimulateResponse> {
+    query(
+        deps,
+        mock_env(),
+        QueryMsg::ReverseSimulate {
+            pair_id: Uint128::zero(),
+            side,
+            desired_receive: Uint128::new(desired_receive),
+        },
+    )
+    .map(|res| from_binary(&res).unwrap())
+}
+
+#[test]
+fn reverse_simulations_stop_at_the_target() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "bob", "uluna", 200);
+    deposit(deps.as_mut(), "carol", "uusd", 500);
+    place(deps.as_mut(), &order("b1", "bob", 0, false, price(3), 100));
+    place(deps.as_mut(), &order("b2", "bob", 0, false, price(2), 100));
+    place(deps.as_mut(), &order("c1", "carol", 0, true, price(3), 100));
+    place(deps.as_mut(), &order("c2", "carol", 0, true, price(2), 100));
+
+    // buying 150 uluna takes all of b2 and half of b1
+    let res = reverse_simulate(deps.as_ref(), Side::Buy, 150).unwrap();
+    assert_eq!(res.fills.len(), 2);
+    assert_eq!(res.offer_amount, Uint128::new(350));
+    assert_eq!(res.receive_amount, Uint128::new(150));
+    assert_eq!(res.average_price, Some(Uint128::new(2_333_333)));
+
+    // receiving 350 uusd sells all of c1 and what covers the rest to c2
+    let res = reverse_simulate(deps.as_ref(), Side::Sell, 350).unwrap();
+    assert_eq!(res.fills.len(), 2);
+    assert_eq!(res.fills[1].quantity, Uint128::new(25));
+    assert_eq!(res.offer_amount, Uint128::new(125));
+    assert_eq!(res.receive_amount, Uint128::new(350));
+
+    // a target met by the first maker reads no further
+    let res = reverse_simulate(deps.as_ref(), Side::Sell, 300).unwrap();
+    assert_eq!(res.fills.len(), 1);
+    assert_eq!(res.offer_amount, Uint128::new(100));
+
+    // more than the book holds can not be received
+    reverse_simulate(deps.as_ref(), Side::Buy, 201).unwrap_err();
+    reverse_simulate(deps.as_ref(), Side::Sell, 501).unwrap_err();
+    // and a target too large to price is an error, not a panic
+    reverse_simulate(deps.as_ref(), Side::Sell, u128::MAX / 1000).unwrap_err();
+}
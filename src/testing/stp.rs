@@ -0,0 +1,96 @@
+use cosmwasm_std::{from_binary, Uint128};
+
+use crate::msg::{CounterOrder, ExecuteMsg, MatchStatus, OrderResponse, SelfTradePrevention};
+use crate::testing::mock_querier::mock_dependencies;
+use crate::testing::{
+    balance, deposit, has_attribute, order, place, price, resting, run, setup_ledger, take, OWNER,
+};
+
+#[test]
+fn self_trade_prevention() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "alice", "uluna", 100);
+    deposit(deps.as_mut(), "alice", "uusd", 200);
+    place(
+        deps.as_mut(),
+        &order("a1", "alice", 0, false, price(2), 100),
+    );
+
+    // the default cancels the taker and leaves the resting order alone
+    let taker = order("a2", "alice", 0, true, price(2), 100);
+    let res = take(deps.as_mut(), &taker, &["a1"], None);
+    let data: OrderResponse = from_binary(res.data.as_ref().unwrap()).unwrap();
+    assert_eq!(data.outcomes[0].status, MatchStatus::SelfTradePrevented);
+    assert!(has_attribute(&res, "self_trade_prevented", "a1"));
+    assert_eq!(resting(deps.as_ref(), false), vec![("a1".to_string(), 100)]);
+    assert_eq!(balance(deps.as_ref(), "alice", "uluna"), (0, 100));
+
+    // decrement and cancel shrinks the resting order by the overlap
+    let small = order("a3", "alice", 0, true, price(2), 30);
+    let msg = ExecuteMsg::Order {
+        order: small,
+        add_order: None,
+        update_order: Some(CounterOrder {
+            order_id: "a1".to_string(),
+            quantity: Uint128::new(30),
+        }),
+        remove_orders: None,
+        self_trade_prevention: Some(SelfTradePrevention::DecrementAndCancel),
+    };
+    run(deps.as_mut(), "alice", msg).unwrap();
+    assert_eq!(resting(deps.as_ref(), false), vec![("a1".to_string(), 70)]);
+    assert!(resting(deps.as_ref(), true).is_empty());
+    assert_eq!(balance(deps.as_ref(), "alice", "uluna"), (30, 70));
+
+    // cancel oldest takes the resting order off and releases its lock
+    take(
+        deps.as_mut(),
+        &taker,
+        &["a1"],
+        Some(SelfTradePrevention::CancelOldest),
+    );
+    assert!(resting(deps.as_ref(), false).is_empty());
+    assert_eq!(balance(deps.as_ref(), "alice", "uluna"), (100, 0));
+    assert_eq!(balance(deps.as_ref(), "alice", "uusd"), (200, 0));
+}
+
+#[test]
+fn pair_default_applies_without_an_order_mode() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "alice", "uluna", 100);
+    deposit(deps.as_mut(), "alice", "uusd", 200);
+    place(
+        deps.as_mut(),
+        &order("a1", "alice", 0, false, price(2), 100),
+    );
+    run(
+        deps.as_mut(),
+        OWNER,
+        ExecuteMsg::UpdatePair {
+            pair_id: Uint128::zero(),
+            status: None,
+            self_trade_prevention: Some(SelfTradePrevention::CancelBoth),
+            price_band: None,
+            circuit_breaker: None,
+            token_transfer_mode: None,
+        },
+    )
+    .unwrap();
+
+    // cancel both drops the resting order and the rest of the taker
+    let taker = order("a2", "alice", 0, true, price(2), 100);
+    let msg = ExecuteMsg::Order {
+        order: taker.clone(),
+        add_order: Some(taker),
+        update_order: None,
+        remove_orders: Some(vec!["a1".to_string()]),
+        self_trade_prevention: None,
+    };
+    run(deps.as_mut(), "alice", msg).unwrap();
+    assert!(resting(deps.as_ref(), false).is_empty());
+    assert!(resting(deps.as_ref(), true).is_empty());
+    assert_eq!(balance(deps.as_ref(), "alice", "uluna"), (100, 0));
+    assert_eq!(balance(deps.as_ref(), "alice", "uusd"), (200, 0));
+}
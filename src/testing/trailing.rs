@@ -0,0 +1,101 @@
+use cosmwasm_std::testing::mock_env;
+use cosmwasm_std::{from_binary, Deps, DepsMut, Uint128};
+
+use crate::contract::query;
+use crate::error::ContractError;
+use crate::msg::{
+    ExecuteMsg, OrderType, QueryMsg, TrailingOffset, TriggerInfo, TriggerListResponse,
+};
+use crate::testing::mock_querier::mock_dependencies;
+use crate::testing::{
+    balance, deposit, fill, has_attribute, order, place, price, run, setup_ledger,
+};
+
+fn place_trailing(deps: DepsMut, offset: TrailingOffset) -> Result<(), ContractError> {
+    run(
+        deps,
+        "alice",
+        ExecuteMsg::PlaceTrailingStop {
+            order: order("a1", "alice", 0, false, Uint128::zero(), 10),
+            offset,
+            order_type: OrderType::Market,
+        },
+    )
+    .map(|_| ())
+}
+
+fn trailing_trigger(deps: Deps) -> TriggerInfo {
+    let res: TriggerListResponse = from_binary(
+        &query(
+            deps,
+            mock_env(),
+            QueryMsg::ListTriggers {
+                pair_id: Uint128::zero(),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    res.triggers.into_iter().next().unwrap()
+}
+
+#[test]
+fn trailing_stop_needs_a_last_price() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "alice", "uluna", 10);
+
+    let err = place_trailing(deps.as_mut(), TrailingOffset::Absolute(price(1))).unwrap_err();
+    assert_eq!(err, ContractError::NotStarted {});
+}
+
+#[test]
+fn trailing_stop_follows_the_price_and_fires_on_a_retrace() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "bob", "uusd", 1000);
+    deposit(deps.as_mut(), "carol", "uluna", 100);
+    deposit(deps.as_mut(), "alice", "uluna", 10);
+    deposit(deps.as_mut(), "dave", "uluna", 20);
+    deposit(deps.as_mut(), "dave", "uusd", 100);
+    place(deps.as_mut(), &order("b1", "bob", 0, true, price(2), 100));
+    place(
+        deps.as_mut(),
+        &order("c1", "carol", 0, false, price(3), 100),
+    );
+    fill(
+        deps.as_mut(),
+        &order("d1", "dave", 0, false, price(2), 10),
+        "b1",
+    );
+
+    let zero = TrailingOffset::Absolute(Uint128::zero());
+    let err = place_trailing(deps.as_mut(), zero).unwrap_err();
+    assert_eq!(err, ContractError::InvalidZeroAmount {});
+    place_trailing(deps.as_mut(), TrailingOffset::Absolute(price(1))).unwrap();
+    let trigger = trailing_trigger(deps.as_ref());
+    assert_eq!(trigger.trailing.unwrap().mark, price(2));
+    assert_eq!(trigger.trigger_price, price(1));
+
+    // a rise to 3 lifts the mark, and the stop with it
+    fill(
+        deps.as_mut(),
+        &order("d2", "dave", 0, true, price(3), 10),
+        "c1",
+    );
+    let trigger = trailing_trigger(deps.as_ref());
+    assert_eq!(trigger.trailing.unwrap().mark, price(3));
+    assert_eq!(trigger.trigger_price, price(2));
+
+    // the retrace to 2 fires it into bob's buy
+    let res = fill(
+        deps.as_mut(),
+        &order("d3", "dave", 0, false, price(2), 10),
+        "b1",
+    );
+    assert!(has_attribute(&res, "triggered", "a1"));
+    assert_eq!(balance(deps.as_ref(), "alice", "uluna"), (0, 0));
+    assert_eq!(balance(deps.as_ref(), "alice", "uusd"), (20, 0));
+}
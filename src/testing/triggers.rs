@@ -0,0 +1,184 @@
+use cosmwasm_std::{attr, Attribute, DepsMut, Response, Uint128};
+
+use crate::msg::{ExecuteMsg, OrderType, TriggerType};
+use crate::testing::mock_querier::mock_dependencies;
+use crate::testing::{
+    balance, deposit, fill, has_attribute, native, order, place, price, resting, run, setup_ledger,
+    triggers,
+};
+
+#[test]
+fn stop_loss_fires_against_the_book() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "bob", "uusd", 300);
+    deposit(deps.as_mut(), "alice", "uluna", 100);
+    deposit(deps.as_mut(), "dave", "uluna", 50);
+
+    place(deps.as_mut(), &order("b1", "bob", 0, true, price(2), 150));
+    run(
+        deps.as_mut(),
+        "alice",
+        ExecuteMsg::PlaceTrigger {
+            order: order("a1", "alice", 0, false, Uint128::zero(), 100),
+            trigger_type: TriggerType::StopLoss,
+            trigger_price: price(2),
+            order_type: OrderType::Market,
+        },
+    )
+    .unwrap();
+    assert_eq!(triggers(deps.as_ref(), 0), vec![("a1".to_string(), 100)]);
+
+    // dave's fill moves the last price to the stop, which sells into the rest of bob's buy
+    let res = fill(
+        deps.as_mut(),
+        &order("d1", "dave", 0, false, price(2), 50),
+        "b1",
+    );
+    assert!(has_attribute(&res, "triggered", "a1"));
+    assert!(triggers(deps.as_ref(), 0).is_empty());
+    assert!(resting(deps.as_ref(), true).is_empty());
+
+    assert_eq!(balance(deps.as_ref(), "alice", "uluna"), (0, 0));
+    assert_eq!(balance(deps.as_ref(), "alice", "uusd"), (200, 0));
+    assert_eq!(balance(deps.as_ref(), "bob", "uluna"), (150, 0));
+    assert_eq!(balance(deps.as_ref(), "bob", "uusd"), (0, 0));
+}
+
+#[test]
+fn unfunded_trigger_is_dropped() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "bob", "uusd", 300);
+    deposit(deps.as_mut(), "alice", "uluna", 100);
+    deposit(deps.as_mut(), "dave", "uluna", 50);
+
+    place(deps.as_mut(), &order("b1", "bob", 0, true, price(2), 150));
+    run(
+        deps.as_mut(),
+        "alice",
+        ExecuteMsg::PlaceTrigger {
+            order: order("a1", "alice", 0, false, Uint128::zero(), 100),
+            trigger_type: TriggerType::StopLoss,
+            trigger_price: price(2),
+            order_type: OrderType::Market,
+        },
+    )
+    .unwrap();
+    // triggers lock nothing, so the funds can leave before it fires
+    run(
+        deps.as_mut(),
+        "alice",
+        ExecuteMsg::Withdraw {
+            asset: native("uluna"),
+            amount: Uint128::new(100),
+        },
+    )
+    .unwrap();
+
+    // the taker that moved the price still trades
+    let res = fill(
+        deps.as_mut(),
+        &order("d1", "dave", 0, false, price(2), 50),
+        "b1",
+    );
+    assert!(has_attribute(&res, "trigger_dropped", "a1"));
+    assert!(triggers(deps.as_ref(), 0).is_empty());
+    assert_eq!(resting(deps.as_ref(), true), vec![("b1".to_string(), 100)]);
+    assert_eq!(balance(deps.as_ref(), "dave", "uusd"), (100, 0));
+}
+
+fn place_stop(deps: DepsMut, id: &str, trigger_price: Uint128) {
+    run(
+        deps,
+        "alice",
+        ExecuteMsg::PlaceTrigger {
+            order: order(id, "alice", 0, false, Uint128::zero(), 10),
+            trigger_type: TriggerType::StopLoss,
+            trigger_price,
+            order_type: OrderType::Market,
+        },
+    )
+    .unwrap();
+}
+
+// dave sells 10 into bob's buy at 1
+fn trade_at_one(deps: DepsMut) -> Response {
+    fill(deps, &order("d1", "dave", 0, false, price(1), 10), "b1")
+}
+
+fn fired(res: &Response) -> Vec<Attribute> {
+    res.attributes
+        .iter()
+        .filter(|attr| attr.key == "triggered")
+        .cloned()
+        .collect()
+}
+
+#[test]
+fn crossed_triggers_fire_by_price_then_placement() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "bob", "uusd", 1000);
+    deposit(deps.as_mut(), "alice", "uluna", 40);
+    deposit(deps.as_mut(), "dave", "uluna", 10);
+    place(deps.as_mut(), &order("b1", "bob", 0, true, price(1), 1000));
+
+    // the id does not decide the turn, the price a stop sits at and then its age do
+    place_stop(deps.as_mut(), "b", price(2));
+    place_stop(deps.as_mut(), "a", price(2));
+    place_stop(deps.as_mut(), "c", price(3));
+    // a take profit of a sell waits for the price to rise
+    run(
+        deps.as_mut(),
+        "alice",
+        ExecuteMsg::PlaceTrigger {
+            order: order("z", "alice", 0, false, Uint128::zero(), 10),
+            trigger_type: TriggerType::TakeProfit,
+            trigger_price: price(5),
+            order_type: OrderType::Market,
+        },
+    )
+    .unwrap();
+
+    let res = trade_at_one(deps.as_mut());
+    assert_eq!(
+        fired(&res),
+        vec![
+            attr("triggered", "c"),
+            attr("triggered", "b"),
+            attr("triggered", "a")
+        ]
+    );
+    assert_eq!(triggers(deps.as_ref(), 0), vec![("z".to_string(), 10)]);
+}
+
+#[test]
+fn execute_triggers_fires_up_to_the_limit() {
+    let mut deps = mock_dependencies();
+    setup_ledger(&mut deps);
+    deposit(deps.as_mut(), "bob", "uusd", 1000);
+    deposit(deps.as_mut(), "alice", "uluna", 30);
+    deposit(deps.as_mut(), "dave", "uluna", 10);
+    place(deps.as_mut(), &order("b1", "bob", 0, true, price(1), 1000));
+    trade_at_one(deps.as_mut());
+
+    // placed below an already crossed price, they fire on the next pass
+    place_stop(deps.as_mut(), "a", price(2));
+    place_stop(deps.as_mut(), "b", price(2));
+    place_stop(deps.as_mut(), "c", price(3));
+    let res = run(
+        deps.as_mut(),
+        "carol",
+        ExecuteMsg::ExecuteTriggers {
+            pair_id: Uint128::zero(),
+            limit: Some(2),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        fired(&res),
+        vec![attr("triggered", "c"), attr("triggered", "a")]
+    );
+    assert_eq!(triggers(deps.as_ref(), 0), vec![("b".to_string(), 10)]);
+}
@@ -0,0 +1,628 @@
+use cosmwasm_std::{
+    attr, Api, Attribute, Decimal, DepsMut, Env, Event, MessageInfo, Order, QuerierWrapper,
+    Response, StdResult, Storage, Uint128,
+};
+
+use crate::contract::{add_orderbook, check_enabled, check_order_funds, get_pair_info};
+use crate::error::ContractError;
+use crate::events::{self, Fill};
+use crate::group::{check_group_id, on_order_cancelled, on_trigger_fired};
+use crate::ledger::{asset_denom, asset_key, free_balance, ledger_enabled, order_commitment};
+use crate::matching::{
+    apply_book_fills, plan_book_fills, plan_funded_fills, record_last_price, settle_fill, BookPlan,
+    NetTransfers,
+};
+use crate::msg::{OrderType, TrailingOffset, TrailingStop, TriggerType};
+use crate::pair::{
+    check_circuit_breaker, check_pair_status, check_price_band, load_pair_settings, PairAction,
+};
+use crate::state::{
+    pair_key, side_key, TriggerOrder, CONFIG, LAST_PRICES, PRIORITY_SEQ, TRAILING_STOPS,
+    TRIGGERS, TRIGGER_INDEX,
+};
+use crate::util;
+
+use cw20::Denom;
+use cw_storage_plus::{Bound, U64Key, U8Key};
+
+use classic_terrapexc::trading::{PairInfo, TraderRecord};
+
+// triggers fired per pass
+pub const DEFAULT_TRIGGER_LIMIT: u32 = 10;
+pub const MAX_TRIGGER_LIMIT: u32 = 30;
+// trailing stops moved per fill
+pub const MAX_TRAILING_UPDATES: usize = 30;
+
+// attributes and events of a trigger pass
+pub type TriggerPass = (Vec<Attribute>, Vec<Event>);
+
+//////////////////////////////////////////////////
+// Description:  Rest an order in the trigger book
+// Params: [1] - Order to place once triggered
+//         [2] - Stop loss or take profit
+//         [3] - Last traded price that fires the order
+//         [4] - Market or limit order once fired
+/////////////////////////////////////////////////
+pub fn execute_place_trigger(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    order: TraderRecord,
+    trigger_type: TriggerType,
+    trigger_price: Uint128,
+    order_type: OrderType,
+) -> Result<Response, ContractError> {
+    check_enabled(&deps, &info)?;
+    let cfg = CONFIG.load(deps.storage)?;
+    let pair_info = get_pair_info(&cfg.pair_list, order.pair_id)?;
+    check_pair_status(deps.storage, order.pair_id, PairAction::Place)?;
+    check_circuit_breaker(deps.storage, env.block.time.seconds(), order.pair_id)?;
+
+    if order.address != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if order.current_stock_amount.is_zero() || trigger_price.is_zero() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+    // trailing stops carry their own mark, see execute_place_trailing_stop
+    if trigger_type == TriggerType::TrailingStop {
+        return Err(ContractError::InvalidInput {});
+    }
+    if order_type == OrderType::Limit && order.price.is_zero() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+    if order_type == OrderType::Limit {
+        check_price_band(deps.storage, order.pair_id, order.price)?;
+    }
+    check_order_funds(
+        deps.storage,
+        &deps.querier,
+        deps.api,
+        &env,
+        &pair_info,
+        &order,
+    )?;
+
+    check_group_id(deps.storage, &order.id)?;
+    let key = (pair_key(order.pair_id), order.id.clone());
+    if TRIGGERS.has(deps.storage, key) {
+        return Err(ContractError::AlreadyStarted {});
+    }
+    add_trigger(
+        deps.storage,
+        TriggerOrder {
+            order: order.clone(),
+            trigger_type,
+            trigger_price,
+            order_type,
+            trailing: None,
+            sequence: 0,
+        },
+    )?;
+
+    Ok(Response::new()
+        .add_event(events::order_placed(&order, "trigger"))
+        .add_attributes(vec![
+            attr("action", "place_trigger"),
+            attr("address", order.address),
+            attr("order_id", order.id),
+        ]))
+}
+
+//////////////////////////////////////////////////
+// Description:  Rest a trailing stop in the trigger book
+// Params: [1] - Order to place once triggered
+//         [2] - Absolute or percentage distance from the mark
+//         [3] - Market or limit order once fired
+/////////////////////////////////////////////////
+pub fn execute_place_trailing_stop(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    order: TraderRecord,
+    offset: TrailingOffset,
+    order_type: OrderType,
+) -> Result<Response, ContractError> {
+    check_enabled(&deps, &info)?;
+    let cfg = CONFIG.load(deps.storage)?;
+    let pair_info = get_pair_info(&cfg.pair_list, order.pair_id)?;
+    check_pair_status(deps.storage, order.pair_id, PairAction::Place)?;
+    check_circuit_breaker(deps.storage, env.block.time.seconds(), order.pair_id)?;
+
+    if order.address != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if order.current_stock_amount.is_zero() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+    if order_type == OrderType::Limit && order.price.is_zero() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+    if order_type == OrderType::Limit {
+        check_price_band(deps.storage, order.pair_id, order.price)?;
+    }
+    match offset {
+        TrailingOffset::Absolute(amount) if amount.is_zero() => {
+            return Err(ContractError::InvalidZeroAmount {})
+        }
+        TrailingOffset::Percent(ratio) if ratio.is_zero() || ratio >= Decimal::one() => {
+            return Err(ContractError::InvalidInput {})
+        }
+        _ => {}
+    }
+    check_order_funds(
+        deps.storage,
+        &deps.querier,
+        deps.api,
+        &env,
+        &pair_info,
+        &order,
+    )?;
+
+    // the mark starts at the last traded price, so the pair must have traded
+    let mark = match LAST_PRICES.may_load(deps.storage, pair_key(order.pair_id))? {
+        Some(price) => price,
+        None => return Err(ContractError::NotStarted {}),
+    };
+
+    check_group_id(deps.storage, &order.id)?;
+    let key = (pair_key(order.pair_id), order.id.clone());
+    if TRIGGERS.has(deps.storage, key) {
+        return Err(ContractError::AlreadyStarted {});
+    }
+    let trailing = TrailingStop { offset, mark };
+    let trigger = TriggerOrder {
+        order: order.clone(),
+        trigger_type: TriggerType::TrailingStop,
+        trigger_price: trailing_trigger_price(&trailing, order.is_buy),
+        order_type,
+        trailing: Some(trailing),
+        sequence: 0,
+    };
+    add_trigger(deps.storage, trigger)?;
+
+    Ok(Response::new()
+        .add_event(events::order_placed(&order, "trigger"))
+        .add_attributes(vec![
+            attr("action", "place_trailing_stop"),
+            attr("address", order.address),
+            attr("order_id", order.id),
+        ]))
+}
+
+pub fn execute_cancel_trigger(
+    deps: DepsMut,
+    info: MessageInfo,
+    pair_id: Uint128,
+    order_id: String,
+) -> Result<Response, ContractError> {
+    let key = (pair_key(pair_id), order_id.clone());
+    let trigger = match TRIGGERS.may_load(deps.storage, key)? {
+        Some(trigger) => trigger,
+        None => return Err(ContractError::NotFindOrder {}),
+    };
+    if trigger.order.address != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    check_pair_status(deps.storage, pair_id, PairAction::Cancel)?;
+    remove_trigger(deps.storage, &trigger);
+    on_order_cancelled(deps.storage, &trigger.order)?;
+
+    Ok(Response::new()
+        .add_event(events::order_cancelled(&trigger.order, "user"))
+        .add_attributes(vec![
+            attr("action", "cancel_trigger"),
+            attr("order_id", order_id),
+        ]))
+}
+
+pub fn execute_execute_triggers(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    pair_id: Uint128,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    check_enabled(&deps, &info)?;
+    check_pair_status(deps.storage, pair_id, PairAction::Match)?;
+    let now = env.block.time.seconds();
+    check_circuit_breaker(deps.storage, now, pair_id)?;
+    let cfg = CONFIG.load(deps.storage)?;
+    let pair_info = get_pair_info(&cfg.pair_list, pair_id)?;
+    let limit = limit
+        .unwrap_or(DEFAULT_TRIGGER_LIMIT)
+        .min(MAX_TRIGGER_LIMIT);
+
+    let mut transfers = NetTransfers::new(&env.contract.address);
+    let (attributes, events) = process_triggers(
+        deps.storage,
+        deps.api,
+        deps.querier,
+        &env,
+        &mut transfers,
+        &pair_info,
+        pair_id,
+        limit,
+    )?;
+    let messages = transfers.into_messages(deps.storage)?;
+
+    Ok(Response::new()
+        .add_submessages(messages)
+        .add_attribute("action", "execute_triggers")
+        .add_attributes(attributes)
+        .add_events(events))
+}
+
+// Whether the price has to rise to the trigger price to fire it, or else fall to it.
+fn fires_on_rise(trigger: &TriggerOrder) -> bool {
+    matches!(
+        (trigger.trigger_type, trigger.order.is_buy),
+        (TriggerType::StopLoss, true)
+            | (TriggerType::TrailingStop, true)
+            | (TriggerType::TakeProfit, false)
+    )
+}
+
+// A sell stop trails below its high-water mark, a buy stop above its low-water mark.
+fn trailing_trigger_price(trailing: &TrailingStop, is_buy: bool) -> Uint128 {
+    let distance = match trailing.offset {
+        TrailingOffset::Absolute(amount) => amount,
+        TrailingOffset::Percent(ratio) => trailing.mark * ratio,
+    };
+    if is_buy {
+        trailing.mark + distance
+    } else {
+        trailing.mark.saturating_sub(distance)
+    }
+}
+
+// Moves the marks of the pair's trailing stops when `price` improves on them.
+// Sell stops trail a rising price and buy stops a falling one, up to MAX_TRAILING_UPDATES
+// of each side move per fill, the ones furthest behind the price first.
+pub fn update_trailing_stops(
+    storage: &mut dyn Storage,
+    pair_id: Uint128,
+    price: Uint128,
+) -> StdResult<()> {
+    let mut trailing_ids = TRAILING_STOPS
+        .prefix((pair_key(pair_id), side_key(false)))
+        .range(
+            storage,
+            None,
+            Some(Bound::exclusive(price.u128().to_be_bytes().to_vec())),
+            Order::Ascending,
+        )
+        .take(MAX_TRAILING_UPDATES)
+        .map(|item| item.map(|(_, id)| id))
+        .collect::<StdResult<Vec<String>>>()?;
+    if let Some(above) = price.u128().checked_add(1) {
+        let buy_ids = TRAILING_STOPS
+            .prefix((pair_key(pair_id), side_key(true)))
+            .range(
+                storage,
+                Some(Bound::inclusive(above.to_be_bytes().to_vec())),
+                None,
+                Order::Descending,
+            )
+            .take(MAX_TRAILING_UPDATES)
+            .map(|item| item.map(|(_, id)| id))
+            .collect::<StdResult<Vec<String>>>()?;
+        trailing_ids.extend(buy_ids);
+    }
+
+    for trailing_id in trailing_ids {
+        let key = (pair_key(pair_id), trailing_id);
+        let mut trigger = TRIGGERS.load(storage, key.clone())?;
+        TRAILING_STOPS.remove(storage, trailing_key(&trigger));
+        TRIGGER_INDEX.remove(storage, trigger_index_key(&trigger));
+        let is_buy = trigger.order.is_buy;
+        if let Some(trailing) = trigger.trailing.as_mut() {
+            trailing.mark = price;
+            trigger.trigger_price = trailing_trigger_price(trailing, is_buy);
+        }
+        TRIGGERS.save(storage, key, &trigger)?;
+        index_trigger(storage, &trigger)?;
+    }
+
+    Ok(())
+}
+
+fn trailing_key(trigger: &TriggerOrder) -> (U64Key, U8Key, Vec<u8>) {
+    let mark = trigger
+        .trailing
+        .as_ref()
+        .map(|trailing| trailing.mark)
+        .unwrap_or_default();
+    let mut key = mark.u128().to_be_bytes().to_vec();
+    key.extend_from_slice(trigger.order.id.as_bytes());
+    (
+        pair_key(trigger.order.pair_id),
+        side_key(trigger.order.is_buy),
+        key,
+    )
+}
+
+// Triggers firing on a falling price fire from the highest trigger price down, the ones
+// firing on a rising price from the lowest up, and in placement order within a price.
+fn trigger_index_key(trigger: &TriggerOrder) -> (U64Key, U8Key, Vec<u8>) {
+    let rises = fires_on_rise(trigger);
+    let price = if rises {
+        trigger.trigger_price.u128()
+    } else {
+        u128::MAX - trigger.trigger_price.u128()
+    };
+    let mut key = price.to_be_bytes().to_vec();
+    key.extend_from_slice(&trigger.sequence.to_be_bytes());
+    (
+        pair_key(trigger.order.pair_id),
+        U8Key::new(rises as u8),
+        key,
+    )
+}
+
+// Files the trigger under its trigger price, and a trailing stop also under its mark.
+pub fn index_trigger(storage: &mut dyn Storage, trigger: &TriggerOrder) -> StdResult<()> {
+    TRIGGER_INDEX.save(storage, trigger_index_key(trigger), &trigger.order.id)?;
+    if trigger.trailing.is_none() {
+        return Ok(());
+    }
+    TRAILING_STOPS.save(storage, trailing_key(trigger), &trigger.order.id)
+}
+
+// Puts a new trigger into the trigger book behind the ones placed before it.
+pub fn add_trigger(storage: &mut dyn Storage, mut trigger: TriggerOrder) -> StdResult<()> {
+    let sequence = PRIORITY_SEQ.may_load(storage)?.unwrap_or_default() + 1;
+    PRIORITY_SEQ.save(storage, &sequence)?;
+    trigger.sequence = sequence;
+    TRIGGERS.save(
+        storage,
+        (pair_key(trigger.order.pair_id), trigger.order.id.clone()),
+        &trigger,
+    )?;
+    index_trigger(storage, &trigger)
+}
+
+// Takes the trigger out of the trigger book and its indexes.
+pub fn remove_trigger(storage: &mut dyn Storage, trigger: &TriggerOrder) {
+    TRIGGERS.remove(
+        storage,
+        (pair_key(trigger.order.pair_id), trigger.order.id.clone()),
+    );
+    TRIGGER_INDEX.remove(storage, trigger_index_key(trigger));
+    if trigger.trailing.is_some() {
+        TRAILING_STOPS.remove(storage, trailing_key(trigger));
+    }
+}
+
+// The next trigger of the pair `last_price` has crossed, the older one if both directions
+// have one. Only the first crossed entry of each direction is read.
+fn next_triggered(
+    storage: &dyn Storage,
+    pair_id: Uint128,
+    last_price: Uint128,
+) -> StdResult<Option<TriggerOrder>> {
+    let price = last_price.u128();
+    // falls fire at or above the price, rises at or below it
+    let ends = [
+        (false, (u128::MAX - price).checked_add(1)),
+        (true, price.checked_add(1)),
+    ];
+    let mut next: Option<(u64, String)> = None;
+    for (rises, end) in ends {
+        let first = TRIGGER_INDEX
+            .prefix((pair_key(pair_id), U8Key::new(rises as u8)))
+            .range(
+                storage,
+                None,
+                end.map(|end| Bound::exclusive(end.to_be_bytes().to_vec())),
+                Order::Ascending,
+            )
+            .next()
+            .transpose()?;
+        if let Some((key, id)) = first {
+            let mut sequence = [0u8; 8];
+            sequence.copy_from_slice(&key[16..24]);
+            let sequence = u64::from_be_bytes(sequence);
+            match next {
+                Some((older, _)) if older < sequence => {}
+                _ => next = Some((sequence, id)),
+            }
+        }
+    }
+
+    match next {
+        Some((_, id)) => TRIGGERS.may_load(storage, (pair_key(pair_id), id)),
+        None => Ok(None),
+    }
+}
+
+// Fires up to `limit` triggers of the pair against the last traded price, the ones
+// the price crossed first before the ones it crossed later, and the oldest first
+// within a trigger price. Fills of fired market orders move the last price, so later
+// triggers of the same pass are picked against it. Triggers whose owner can no longer
+// cover the order or what its planned fills cost are dropped instead of
+// failing the whole pass, makers that can no longer settle a fill are
+// evicted from the book.
+// The pass stops early once a fill trips the pair's circuit breaker.
+// Fills settle into `transfers`, shared with the caller's own fills.
+#[allow(clippy::too_many_arguments)]
+pub fn process_triggers(
+    storage: &mut dyn Storage,
+    api: &dyn Api,
+    querier: QuerierWrapper,
+    env: &Env,
+    transfers: &mut NetTransfers,
+    pair_info: &PairInfo,
+    pair_id: Uint128,
+    limit: u32,
+) -> Result<TriggerPass, ContractError> {
+    let mut attributes: Vec<Attribute> = vec![];
+    let mut events: Vec<Event> = vec![];
+    let now = env.block.time.seconds();
+
+    // nothing fires while the pair does not match
+    if LAST_PRICES.may_load(storage, pair_key(pair_id))?.is_none()
+        || check_pair_status(storage, pair_id, PairAction::Match).is_err()
+    {
+        return Ok((attributes, events));
+    }
+
+    let mut fired = 0u32;
+    while fired < limit && check_circuit_breaker(storage, now, pair_id).is_ok() {
+        // fills of earlier triggers of the pass move the price the next one is picked at
+        let last_price = LAST_PRICES.load(storage, pair_key(pair_id))?;
+        let trigger = match next_triggered(storage, pair_id, last_price)? {
+            Some(trigger) => trigger,
+            None => break,
+        };
+        fired += 1;
+
+        remove_trigger(storage, &trigger);
+        let order = trigger.order;
+        on_trigger_fired(storage, &order)?;
+        if let Err(err) = check_order_funds(storage, &querier, api, env, pair_info, &order) {
+            attributes.push(attr("trigger_dropped", order.id.clone()));
+            events.push(dropped_trigger(&order, &err));
+            continue;
+        }
+
+        match trigger.order_type {
+            OrderType::Limit => {
+                if let Err(err) = add_orderbook(storage, order.clone()) {
+                    attributes.push(attr("trigger_dropped", order.id.clone()));
+                    events.push(dropped_trigger(&order, &err));
+                    continue;
+                }
+                events.push(events::order_placed(&order, "book"));
+            }
+            OrderType::Market => {
+                let stp = load_pair_settings(storage, pair_id)?.self_trade_prevention;
+                let (plan, evictions) = plan_funded_fills(
+                    storage,
+                    api,
+                    querier,
+                    env,
+                    pair_info,
+                    order.is_buy,
+                    |storage| {
+                        plan_book_fills(
+                            storage,
+                            pair_id,
+                            &order.address,
+                            order.is_buy,
+                            order.current_stock_amount,
+                            None,
+                            stp,
+                        )
+                    },
+                )?;
+                events.extend(evictions);
+                // with the cost covered up front settling the fills below can not fail
+                if let Err(err) = check_planned_cost(
+                    storage, api, querier, env, transfers, pair_info, &order, &plan,
+                ) {
+                    attributes.push(attr("trigger_dropped", order.id.clone()));
+                    events.push(dropped_trigger(&order, &err));
+                    continue;
+                }
+                let refills = apply_book_fills(storage, order.is_buy, &plan, stp)?;
+                for fill in plan.fills.iter() {
+                    let (buyer, seller) = if order.is_buy {
+                        (&order.address, &fill.maker)
+                    } else {
+                        (&fill.maker, &order.address)
+                    };
+                    settle_fill(
+                        storage,
+                        transfers,
+                        pair_info,
+                        buyer,
+                        seller,
+                        fill.quantity,
+                        fill.price,
+                        order.is_buy,
+                    )?;
+                    events.push(events::order_filled(&Fill {
+                        pair_id,
+                        maker_order_id: &fill.order_id,
+                        maker: &fill.maker,
+                        taker_order_id: &order.id,
+                        taker: &order.address,
+                        taker_is_buy: order.is_buy,
+                        price: fill.price,
+                        quantity: fill.quantity,
+                    }));
+                    record_last_price(storage, now, pair_id, fill.price)?;
+                }
+                events.extend(refills);
+            }
+        }
+        attributes.push(attr("triggered", order.id));
+    }
+
+    Ok((attributes, events))
+}
+
+// Checks the owner of a fired market order covers what its planned fills cost, on top of
+// what the batch already pulls from them. Placing the order only checked it at its own price.
+#[allow(clippy::too_many_arguments)]
+fn check_planned_cost(
+    storage: &dyn Storage,
+    api: &dyn Api,
+    querier: QuerierWrapper,
+    env: &Env,
+    transfers: &NetTransfers,
+    pair_info: &PairInfo,
+    order: &TraderRecord,
+    plan: &BookPlan,
+) -> Result<(), ContractError> {
+    let cost = plan
+        .fills
+        .iter()
+        .map(|fill| order_commitment(pair_info, order.is_buy, fill.quantity, fill.price).1)
+        .sum::<Uint128>();
+    let asset = if order.is_buy {
+        &pair_info.from_asset
+    } else {
+        &pair_info.to_asset
+    };
+
+    if ledger_enabled(storage)? {
+        if free_balance(storage, &order.address, &asset_key(asset))? < cost {
+            return Err(ContractError::InsufficientFunds {});
+        }
+        return Ok(());
+    }
+
+    let denom = asset_denom(api, asset)?;
+    let balance = util::get_token_amount(querier, denom.clone(), order.address.clone())?;
+    match denom {
+        Denom::Cw20(token) => {
+            let cost = cost + transfers.pulled(&token, &order.address);
+            if balance < cost {
+                return Err(ContractError::InsufficientFunds {});
+            }
+            let allowance = util::get_token_allowance(
+                querier,
+                token,
+                order.address.clone(),
+                env.contract.address.clone(),
+                &env.block,
+            )?;
+            if allowance < cost {
+                return Err(ContractError::InsufficientAllowance {});
+            }
+        }
+        Denom::Native(_) => {
+            if balance < cost {
+                return Err(ContractError::InsufficientFunds {});
+            }
+        }
+    }
+    Ok(())
+}
+
+// A fired trigger its owner can not settle is dropped, the taker that fired it still trades.
+fn dropped_trigger(order: &TraderRecord, reason: &ContractError) -> Event {
+    events::order_cancelled(order, "evicted").add_attribute("error", reason.to_string())
+}
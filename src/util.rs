@@ -1,9 +1,11 @@
 use crate::error::ContractError;
 use cosmwasm_std::{
-    to_binary, Addr, BalanceResponse as NativeBalanceResponse, BankMsg, BankQuery, Coin, CosmosMsg,
-    QuerierWrapper, QueryRequest, Uint128, WasmMsg, WasmQuery,
+    to_binary, Addr, BalanceResponse as NativeBalanceResponse, BankMsg, BankQuery, BlockInfo, Coin,
+    CosmosMsg, QuerierWrapper, QueryRequest, Uint128, WasmMsg, WasmQuery,
+};
+use cw20::{
+    AllowanceResponse, BalanceResponse as CW20BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg, Denom,
 };
-use cw20::{BalanceResponse as CW20BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg, Denom};
 
 pub fn get_token_amount(
     querier: QuerierWrapper,
@@ -32,6 +34,27 @@ pub fn get_token_amount(
     }
 }
 
+pub fn get_token_allowance(
+    querier: QuerierWrapper,
+    cw20_address: Addr,
+    owner: Addr,
+    spender: Addr,
+    block: &BlockInfo,
+) -> Result<Uint128, ContractError> {
+    let allowance_response: AllowanceResponse =
+        querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+            contract_addr: cw20_address.into(),
+            msg: to_binary(&Cw20QueryMsg::Allowance {
+                owner: owner.into(),
+                spender: spender.into(),
+            })?,
+        }))?;
+    if allowance_response.expires.is_expired(block) {
+        return Ok(Uint128::zero());
+    }
+    Ok(allowance_response.allowance)
+}
+
 pub fn transfer_token_message(
     denom: Denom,
     amount: Uint128,